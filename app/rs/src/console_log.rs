@@ -0,0 +1,204 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+//! Rotation of the container's console log
+//!
+//! init writes its console straight to the log file, so rotation can't
+//! reopen it under the writer. The file is opened in append mode instead
+//! and rotated copy-then-truncate, as logrotate's `copytruncate` does;
+//! lines written between the copy and the truncate are lost.
+//!
+//! Rotated files are gzip-compressed by default, as `log.txt.1.gz`.
+
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use log::{info, warn};
+use once_cell::sync::Lazy;
+use std::fs::{self, File, OpenOptions};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// Size at which the running log is rotated, 0 disables size-based rotation
+static MAX_BYTES: AtomicU64 = AtomicU64::new(4 * 1024 * 1024);
+
+/// Age in seconds at which the running log is rotated, 0 disables time-based rotation
+static MAX_AGE_SECS: AtomicU64 = AtomicU64::new(0);
+
+/// Rotated files kept next to the log, as `log.txt.1` (newest) to `log.txt.<keep>`
+static KEEP: AtomicUsize = AtomicUsize::new(3);
+
+/// Whether rotated files are gzip-compressed
+static COMPRESS: AtomicBool = AtomicBool::new(true);
+
+/// When the running log was started or last rotated
+static STARTED: Lazy<Mutex<Option<Instant>>> = Lazy::new(|| Mutex::new(None));
+
+/// Set when the running log is rotated, how many rotated files to keep and
+/// whether to compress them
+pub fn set_rotation(max_bytes: u64, max_age: Duration, keep: usize, compress: bool) {
+    info!(
+        "[CONTAINER] Log rotation: max {} bytes, max age {:?}, keep {}, compress {}",
+        max_bytes, max_age, keep, compress
+    );
+    MAX_BYTES.store(max_bytes, Ordering::Relaxed);
+    MAX_AGE_SECS.store(max_age.as_secs(), Ordering::Relaxed);
+    KEEP.store(keep, Ordering::Relaxed);
+    COMPRESS.store(compress, Ordering::Relaxed);
+}
+
+fn rotated_path(path: &Path, index: usize, compressed: bool) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(format!(".{}", index));
+    if compressed {
+        name.push(".gz");
+    }
+    PathBuf::from(name)
+}
+
+/// Shift `path.1`..`path.<keep - 1>` up by one, dropping the oldest
+///
+/// Both plain and compressed files are shifted, so changing the compression
+/// setting does not orphan older logs.
+fn shift_rotated(path: &Path, keep: usize) {
+    for compressed in [false, true] {
+        let _ = fs::remove_file(rotated_path(path, keep, compressed));
+        for index in (1..keep).rev() {
+            let _ = fs::rename(rotated_path(path, index, compressed), rotated_path(path, index + 1, compressed));
+        }
+    }
+}
+
+/// Copy the log to `path.1`, compressing it if enabled
+fn copy_rotated(path: &Path) -> io::Result<()> {
+    if !COMPRESS.load(Ordering::Relaxed) {
+        return fs::copy(path, rotated_path(path, 1, false)).map(|_| ());
+    }
+    let mut source = File::open(path)?;
+    let target = File::create(rotated_path(path, 1, true))?;
+    let mut encoder = GzEncoder::new(target, Compression::default());
+    io::copy(&mut source, &mut encoder)?;
+    encoder.finish()?;
+    Ok(())
+}
+
+/// Whether the running log has passed the size or age limit
+fn needs_rotation(size: u64, age: Duration) -> bool {
+    let max_bytes = MAX_BYTES.load(Ordering::Relaxed);
+    let max_age = MAX_AGE_SECS.load(Ordering::Relaxed);
+    (max_bytes > 0 && size > max_bytes) || (max_age > 0 && age.as_secs() >= max_age)
+}
+
+/// Start a fresh log for a new container run, keeping the previous run's log
+pub fn open(path: &Path) -> io::Result<File> {
+    let keep = KEEP.load(Ordering::Relaxed);
+    if keep > 0 && path.exists() {
+        shift_rotated(path, keep);
+        if let Err(e) = copy_rotated(path) {
+            warn!("[CONTAINER] Failed to rotate {}: {}", path.display(), e);
+        }
+    }
+
+    let file = OpenOptions::new().create(true).append(true).open(path)?;
+    file.set_len(0)?;
+    *STARTED.lock().unwrap() = Some(Instant::now());
+    Ok(file)
+}
+
+/// Rotate the running log once it has grown past the size limit or aged past the time limit
+pub fn rotate_if_needed(path: &Path) {
+    let size = match fs::metadata(path) {
+        Ok(metadata) => metadata.len(),
+        Err(_) => return,
+    };
+    let age = STARTED.lock().unwrap().map_or(Duration::ZERO, |started| started.elapsed());
+    // An empty log gains nothing from rotating, however old it is
+    if size == 0 || !needs_rotation(size, age) {
+        return;
+    }
+
+    let keep = KEEP.load(Ordering::Relaxed);
+    if keep > 0 {
+        shift_rotated(path, keep);
+        if let Err(e) = copy_rotated(path) {
+            warn!("[CONTAINER] Failed to rotate {}: {}", path.display(), e);
+            return;
+        }
+    }
+    match OpenOptions::new().write(true).open(path).and_then(|file| file.set_len(0)) {
+        Ok(()) => {
+            *STARTED.lock().unwrap() = Some(Instant::now());
+            info!("[CONTAINER] Rotated {}", path.display());
+        }
+        Err(e) => warn!("[CONTAINER] Failed to truncate {}: {}", path.display(), e),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use flate2::read::GzDecoder;
+    use std::io::{Read, Write};
+
+    #[test]
+    fn test_rotated_path() {
+        let path = Path::new("/data/log.txt");
+        assert_eq!(rotated_path(path, 1, false), Path::new("/data/log.txt.1"));
+        assert_eq!(rotated_path(path, 3, true), Path::new("/data/log.txt.3.gz"));
+    }
+
+    fn gunzip(path: &Path) -> String {
+        let mut content = String::new();
+        GzDecoder::new(File::open(path).unwrap()).read_to_string(&mut content).unwrap();
+        content
+    }
+
+    // The rotation settings are global, so one test covers everything that reads them
+    #[test]
+    fn test_rotation() {
+        let dir = std::env::temp_dir().join(format!("twoyi-console-log-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("log.txt");
+
+        set_rotation(16, Duration::ZERO, 2, true);
+        let mut log = open(&path).unwrap();
+
+        // Below the size limit nothing happens
+        log.write_all(b"first").unwrap();
+        rotate_if_needed(&path);
+        assert!(!rotated_path(&path, 1, true).exists());
+
+        for run in ["0123456789abcdef-one", "0123456789abcdef-two", "0123456789abcdef-three"] {
+            log.write_all(run.as_bytes()).unwrap();
+            rotate_if_needed(&path);
+            assert_eq!(fs::metadata(&path).unwrap().len(), 0);
+        }
+        assert_eq!(gunzip(&rotated_path(&path, 1, true)), "0123456789abcdef-three");
+        assert_eq!(gunzip(&rotated_path(&path, 2, true)), "0123456789abcdef-two");
+        assert!(!rotated_path(&path, 3, true).exists());
+
+        // Appends continue at the start of the truncated file
+        log.write_all(b"after").unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "after");
+
+        // A new run keeps the previous log, plain when compression is off
+        set_rotation(0, Duration::ZERO, 2, false);
+        drop(log);
+        let _log = open(&path).unwrap();
+        assert_eq!(fs::read_to_string(rotated_path(&path, 1, false)).unwrap(), "after");
+        assert_eq!(gunzip(&rotated_path(&path, 2, true)), "0123456789abcdef-three");
+        assert_eq!(fs::metadata(&path).unwrap().len(), 0);
+
+        assert!(!needs_rotation(u64::MAX, Duration::from_secs(3600)));
+        set_rotation(0, Duration::from_secs(60), 2, false);
+        assert!(!needs_rotation(1, Duration::from_secs(59)));
+        assert!(needs_rotation(1, Duration::from_secs(60)));
+
+        set_rotation(4 * 1024 * 1024, Duration::ZERO, 3, true);
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,669 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+//! Container rootfs ownership and housekeeping
+//!
+//! Only one process may drive a rootfs at a time: the input servers and the
+//! renderer bind sockets inside it, and a second owner would silently steal
+//! them. Ownership is tracked with an advisory lock file holding the owner PID.
+//!
+//! This module also owns the container's `./init` process and acts on its
+//! whole process tree.
+
+use log::{error, info, warn};
+use std::fs::{File, OpenOptions};
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::os::unix::fs::FileTypeExt;
+use std::ffi::{c_void, CString};
+use std::os::unix::io::{AsRawFd, FromRawFd};
+use std::os::unix::process::CommandExt;
+use std::path::Path;
+use std::process::{Child, Command, ExitStatus, Stdio};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+use std::thread;
+use std::time::{Duration, Instant};
+use once_cell::sync::Lazy;
+
+use crate::console_log;
+use crate::events;
+use crate::seccomp::{self, SeccompFilter, SeccompProfile};
+
+/// Container root filesystem
+pub const ROOTFS_PATH: &str = "/data/data/io.twoyi/rootfs";
+
+/// Lock file guarding the rootfs, contains the owner PID
+const LOCK_PATH: &str = "/data/data/io.twoyi/twoyi.lock";
+
+/// Container console output
+const LOG_PATH: &str = "/data/data/io.twoyi/log.txt";
+
+/// Sockets left behind in the rootfs by a previous run, relative to `ROOTFS_PATH`
+const STALE_SOCKETS: &[&str] = &[
+    "dev/input/touch",
+    "dev/input/key0",
+    "dev/input/keyboard0",
+    "dev/input/gamepad0",
+    "dev/input/mouse0",
+    "dev/input/stylus0",
+    "opengles",
+    "opengles2",
+    "opengles3",
+];
+
+/// The held lock file; the advisory lock is released when the process exits
+static LOCK_FILE: Lazy<Mutex<Option<File>>> = Lazy::new(|| Mutex::new(None));
+
+/// The running container init process
+static INIT_PROCESS: Lazy<Mutex<Option<Child>>> = Lazy::new(|| Mutex::new(None));
+
+/// Hostname given to the container when it gets its own UTS namespace
+static CONTAINER_HOSTNAME: Lazy<Mutex<String>> = Lazy::new(|| Mutex::new(String::from("twoyi")));
+
+/// Host timezone and locale propagated into the ROM at boot, if enabled
+static HOST_TIME_SETTINGS: Lazy<Mutex<Option<(String, String)>>> = Lazy::new(|| Mutex::new(None));
+
+/// Loader the container was last started with, reused on restart
+static LOADER_PATH: Lazy<Mutex<Option<String>>> = Lazy::new(|| Mutex::new(None));
+
+/// Host directories bind-mounted into the container, as (host path, container path)
+static SHARED_FOLDERS: Lazy<Mutex<Vec<(String, String)>>> = Lazy::new(|| Mutex::new(Vec::new()));
+
+/// Set while `stop` is bringing the container down, so the exit is not reported
+static STOPPING: AtomicBool = AtomicBool::new(false);
+
+/// When the current init process was started
+static INIT_STARTED: Lazy<Mutex<Option<Instant>>> = Lazy::new(|| Mutex::new(None));
+
+/// Set once the guest reports it finished booting, cleared when init starts again
+static BOOT_COMPLETED: AtomicBool = AtomicBool::new(false);
+
+/// How often the running console log is checked for rotation, in `watch_init` ticks
+const LOG_CHECK_TICKS: u32 = 30;
+
+/// How long `stop` waits after SIGTERM before killing what is left
+const STOP_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Persistent property store of the ROM, relative to `ROOTFS_PATH`
+const PERSIST_PROPERTY_DIR: &str = "data/property";
+
+/// Set the hostname used for the container's UTS namespace
+pub fn set_hostname(hostname: String) {
+    info!("[CONTAINER] Container hostname set to: {}", hostname);
+    *CONTAINER_HOSTNAME.lock().unwrap() = hostname;
+}
+
+/// Take ownership of the rootfs and clean up after a previous crashed run
+///
+/// Fails if another live process already owns the rootfs.
+pub fn acquire_rootfs() -> io::Result<()> {
+    let mut held = LOCK_FILE.lock().unwrap();
+    if held.is_some() {
+        return Ok(());
+    }
+
+    let mut file = OpenOptions::new()
+        .read(true)
+        .write(true)
+        .create(true)
+        // The owner's pid is read back below, so keep it
+        .truncate(false)
+        .open(LOCK_PATH)?;
+
+    let ret = unsafe { libc::flock(file.as_raw_fd(), libc::LOCK_EX | libc::LOCK_NB) };
+    if ret != 0 {
+        let err = io::Error::last_os_error();
+        let mut owner = String::new();
+        let _ = file.read_to_string(&mut owner);
+        error!("[CONTAINER] Rootfs is owned by another process (pid {}): {}", owner.trim(), err);
+        return Err(io::Error::new(
+            io::ErrorKind::AddrInUse,
+            format!("rootfs owned by pid {}", owner.trim()),
+        ));
+    }
+
+    // We hold the lock, so whatever PID is recorded belongs to a dead run.
+    let mut previous = String::new();
+    let _ = file.read_to_string(&mut previous);
+    if !previous.trim().is_empty() {
+        warn!("[CONTAINER] Taking over rootfs from stale owner pid {}", previous.trim());
+    }
+    cleanup_stale_sockets();
+
+    file.set_len(0)?;
+    file.seek(SeekFrom::Start(0))?;
+    write!(file, "{}", std::process::id())?;
+    file.flush()?;
+
+    info!("[CONTAINER] Rootfs lock acquired by pid {}", std::process::id());
+    *held = Some(file);
+    Ok(())
+}
+
+/// Remove sockets left in the rootfs by a previous run
+///
+/// Only socket files are removed, so a ROM shipping a regular file under one
+/// of these names is left untouched.
+pub fn cleanup_stale_sockets() {
+    for name in STALE_SOCKETS {
+        let path = Path::new(ROOTFS_PATH).join(name);
+        match std::fs::symlink_metadata(&path) {
+            Ok(meta) if meta.file_type().is_socket() => {
+                if let Err(e) = std::fs::remove_file(&path) {
+                    warn!("[CONTAINER] Failed to remove stale socket {:?}: {}", path, e);
+                } else {
+                    info!("[CONTAINER] Removed stale socket {:?}", path);
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Whether this process may create mount namespaces and bind mounts
+fn has_cap_sys_admin() -> bool {
+    const CAP_SYS_ADMIN: u32 = 21;
+    let status = match std::fs::read_to_string("/proc/self/status") {
+        Ok(status) => status,
+        Err(_) => return false,
+    };
+    status
+        .lines()
+        .find_map(|line| line.strip_prefix("CapEff:"))
+        .and_then(|caps| u64::from_str_radix(caps.trim(), 16).ok())
+        .map(|caps| caps & (1 << CAP_SYS_ADMIN) != 0)
+        .unwrap_or(false)
+}
+
+/// Share a host directory with the container from its next start
+///
+/// `container_dir` is absolute inside the container, e.g. `/data/media/0/Download`.
+/// Sharing is a bind mount in the container's mount namespace, which needs
+/// CAP_SYS_ADMIN; a regular app process gets PermissionDenied.
+pub fn add_shared_folder(host_dir: String, container_dir: String) -> io::Result<()> {
+    if !has_cap_sys_admin() {
+        return Err(io::Error::new(
+            io::ErrorKind::PermissionDenied,
+            "sharing folders needs CAP_SYS_ADMIN for bind mounts",
+        ));
+    }
+    if !Path::new(&host_dir).is_dir() {
+        return Err(io::Error::new(io::ErrorKind::NotFound, format!("{} is not a directory", host_dir)));
+    }
+    let target = Path::new(&container_dir);
+    if !target.is_absolute() || target.components().any(|c| c == std::path::Component::ParentDir) {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("{} is not an absolute container path", container_dir),
+        ));
+    }
+
+    info!("[CONTAINER] Sharing {} as {}", host_dir, container_dir);
+    SHARED_FOLDERS.lock().unwrap().push((host_dir, container_dir));
+    Ok(())
+}
+
+/// Create the mount points of the shared folders and resolve them for `isolate`
+fn shared_folder_mounts() -> Vec<(CString, CString)> {
+    let mut mounts = Vec::new();
+    for (host_dir, container_dir) in SHARED_FOLDERS.lock().unwrap().iter() {
+        let target = Path::new(ROOTFS_PATH).join(container_dir.trim_start_matches('/'));
+        if let Err(e) = std::fs::create_dir_all(&target) {
+            warn!("[CONTAINER] Cannot create {}: {}", target.display(), e);
+            continue;
+        }
+        match (CString::new(host_dir.as_str()), CString::new(target.to_string_lossy().as_bytes())) {
+            (Ok(source), Ok(target)) => mounts.push((source, target)),
+            _ => warn!("[CONTAINER] Skipping shared folder {} with a NUL in its path", host_dir),
+        }
+    }
+    mounts
+}
+
+/// Spawn the container's `./init` with its console redirected to the log file
+pub fn start_init(loader_path: &str) -> io::Result<()> {
+    info!("[CONTAINER] Working directory: {}", ROOTFS_PATH);
+    info!("[CONTAINER] Log path: {}", LOG_PATH);
+
+    apply_host_time_settings();
+
+    let outputs = console_log::open(Path::new(LOG_PATH))?;
+    let errors = outputs.try_clone()?;
+    let hostname = CString::new(CONTAINER_HOSTNAME.lock().unwrap().as_str())
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+
+    let mounts = shared_folder_mounts();
+
+    let profile = seccomp::profile();
+    let filter = SeccompFilter::build(profile);
+    info!("[CONTAINER] Seccomp profile: {:?}", profile);
+
+    // The child writes the failed `isolate` steps here before exec
+    let mut report = [0 as libc::c_int; 2];
+    if unsafe { libc::pipe2(report.as_mut_ptr(), libc::O_CLOEXEC) } != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    let (report_read, report_write) = (report[0], report[1]);
+    let child_mounts = mounts.clone();
+
+    let mut command = Command::new("./init");
+    command
+        .current_dir(ROOTFS_PATH)
+        .env("TYLOADER", loader_path)
+        .stdout(Stdio::from(outputs))
+        .stderr(Stdio::from(errors));
+    unsafe {
+        command.pre_exec(move || {
+            let failed = isolate(&hostname, &child_mounts).to_ne_bytes();
+            libc::write(report_write, failed.as_ptr() as *const c_void, failed.len());
+            if let Some(ref filter) = filter {
+                // A strict profile was asked for explicitly, so fail closed
+                if let Err(e) = filter.install() {
+                    if profile == SeccompProfile::Strict {
+                        return Err(e);
+                    }
+                }
+            }
+            Ok(())
+        });
+    }
+    let spawned = command.spawn();
+    unsafe { libc::close(report_write) };
+    let mut report = unsafe { File::from_raw_fd(report_read) };
+    let child = spawned?;
+
+    let mut failed = [0u8; 8];
+    if report.read_exact(&mut failed).is_ok() {
+        log_isolate_failures(u64::from_ne_bytes(failed), &mounts);
+    }
+
+    let pid = child.id();
+    info!("[CONTAINER] Container init started with pid {}", pid);
+    *INIT_PROCESS.lock().unwrap() = Some(child);
+    *INIT_STARTED.lock().unwrap() = Some(Instant::now());
+    BOOT_COMPLETED.store(false, Ordering::Release);
+    *LOADER_PATH.lock().unwrap() = Some(loader_path.to_string());
+
+    thread::spawn(move || watch_init(pid));
+    Ok(())
+}
+
+/// Report when init exits on its own, and keep its console log in bounds
+///
+/// Stops the watch quietly once `stop()` has taken the child or a restart
+/// replaced it, since the caller already knows about those.
+fn watch_init(pid: u32) {
+    let mut ticks = 0u32;
+    loop {
+        thread::sleep(Duration::from_secs(1));
+
+        ticks += 1;
+        if ticks.is_multiple_of(LOG_CHECK_TICKS) {
+            console_log::rotate_if_needed(Path::new(LOG_PATH));
+        }
+
+        let mut init = INIT_PROCESS.lock().unwrap();
+        match init.as_ref() {
+            Some(child) if child.id() == pid => {}
+            _ => return,
+        }
+        if let Some(status) = reap_init(&mut init) {
+            drop(init);
+            report_exit(status);
+            return;
+        }
+    }
+}
+
+/// Reap init if it has exited, returning its status
+///
+/// A reaped init is taken out of `INIT_PROCESS` right away: its PID is free
+/// for reuse from then on and must no longer be treated as the container.
+fn reap_init(init: &mut Option<Child>) -> Option<ExitStatus> {
+    match init.as_mut()?.try_wait() {
+        Ok(Some(status)) => {
+            init.take();
+            Some(status)
+        }
+        Ok(None) => None,
+        Err(e) => {
+            error!("[CONTAINER] Failed to poll init: {}", e);
+            None
+        }
+    }
+}
+
+/// Log an exit found by `reap_init`, called without `INIT_PROCESS` held
+/// since the event listener may query the container again
+fn report_exit(status: ExitStatus) {
+    if STOPPING.load(Ordering::Acquire) {
+        info!("[CONTAINER] Container init exited: {}", status);
+    } else {
+        warn!("[CONTAINER] Container init exited: {}", status);
+        events::emit(events::CONTAINER_EXITED, &status.to_string());
+    }
+}
+
+/// Propagate the host timezone and locale into the ROM on the next boot
+///
+/// Only the timezone and locale are carried over. The container reads the
+/// host kernel's clock, so there is no separate guest clock to drift, and
+/// nothing here corrects the host's own drift.
+pub fn set_host_time_settings(timezone: String, locale: String) {
+    info!("[CONTAINER] Host timezone: {}, locale: {}", timezone, locale);
+    *HOST_TIME_SETTINGS.lock().unwrap() = Some((timezone, locale));
+}
+
+/// Write the host timezone and locale as persistent properties
+///
+/// The ROM keeps one file per persistent property, read by the property
+/// service at boot, so this must run before `./init` starts.
+fn apply_host_time_settings() {
+    let settings = HOST_TIME_SETTINGS.lock().unwrap().clone();
+    let (timezone, locale) = match settings {
+        Some(settings) => settings,
+        None => return,
+    };
+
+    let dir = Path::new(ROOTFS_PATH).join(PERSIST_PROPERTY_DIR);
+    if let Err(e) = std::fs::create_dir_all(&dir) {
+        warn!("[CONTAINER] Failed to create {:?}: {}", dir, e);
+        return;
+    }
+    for (name, value) in [("persist.sys.timezone", timezone), ("persist.sys.locale", locale)] {
+        if value.is_empty() {
+            continue;
+        }
+        if let Err(e) = std::fs::write(dir.join(name), value.as_bytes()) {
+            warn!("[CONTAINER] Failed to write {}: {}", name, e);
+        }
+    }
+}
+
+/// Steps of `isolate` that failed, as reported back to the parent
+const ISOLATE_SESSION: u64 = 1 << 0;
+const ISOLATE_MOUNT_NS: u64 = 1 << 1;
+const ISOLATE_MOUNT_PRIVATE: u64 = 1 << 2;
+const ISOLATE_UTS_NS: u64 = 1 << 3;
+const ISOLATE_HOSTNAME: u64 = 1 << 4;
+/// Shared folder `i` failed to bind at bit `ISOLATE_BIND_SHIFT + i`
+const ISOLATE_BIND_SHIFT: usize = 8;
+
+/// Runs in the forked child right before exec of `./init`
+///
+/// Only a new session plus mount and UTS namespaces are attempted. There is
+/// no PID namespace: `./init` itself would stay outside one created here,
+/// only its children would enter it. The namespaces need CAP_SYS_ADMIN, which
+/// a regular app process does not have, so their failure is expected and
+/// does not stop the container.
+///
+/// Nothing can be logged between fork and exec, so the failed steps are
+/// returned as `ISOLATE_*` bits for the parent to log.
+fn isolate(hostname: &CString, mounts: &[(CString, CString)]) -> u64 {
+    let mut failed = 0;
+    unsafe {
+        if libc::setsid() < 0 {
+            failed |= ISOLATE_SESSION;
+        }
+        if libc::unshare(libc::CLONE_NEWNS) == 0 {
+            // Keep our mounts from propagating back to the host
+            let private = libc::mount(
+                std::ptr::null(),
+                c"/".as_ptr(),
+                std::ptr::null(),
+                libc::MS_REC | libc::MS_PRIVATE,
+                std::ptr::null(),
+            );
+            if private != 0 {
+                failed |= ISOLATE_MOUNT_PRIVATE;
+            }
+            for (index, (source, target)) in mounts.iter().enumerate() {
+                let bound = libc::mount(
+                    source.as_ptr(),
+                    target.as_ptr(),
+                    std::ptr::null(),
+                    libc::MS_BIND | libc::MS_REC,
+                    std::ptr::null(),
+                );
+                if bound != 0 && ISOLATE_BIND_SHIFT + index < 64 {
+                    failed |= 1 << (ISOLATE_BIND_SHIFT + index);
+                }
+            }
+        } else {
+            failed |= ISOLATE_MOUNT_NS;
+        }
+        if libc::unshare(libc::CLONE_NEWUTS) == 0 {
+            if libc::sethostname(hostname.as_ptr(), hostname.as_bytes().len()) != 0 {
+                failed |= ISOLATE_HOSTNAME;
+            }
+        } else {
+            failed |= ISOLATE_UTS_NS;
+        }
+    }
+    failed
+}
+
+/// Log what `isolate` could not set up in the child
+fn log_isolate_failures(failed: u64, mounts: &[(CString, CString)]) {
+    let steps = [
+        (ISOLATE_SESSION, "new session"),
+        (ISOLATE_MOUNT_NS, "mount namespace"),
+        (ISOLATE_MOUNT_PRIVATE, "private mount propagation"),
+        (ISOLATE_UTS_NS, "UTS namespace"),
+        (ISOLATE_HOSTNAME, "hostname"),
+    ];
+    for (bit, step) in steps {
+        if failed & bit != 0 {
+            warn!("[CONTAINER] Isolation: {} not available", step);
+        }
+    }
+    if failed & ISOLATE_MOUNT_NS != 0 && !mounts.is_empty() {
+        error!("[CONTAINER] No mount namespace, {} shared folders not mounted", mounts.len());
+    }
+    for (index, (source, _)) in mounts.iter().enumerate() {
+        if ISOLATE_BIND_SHIFT + index < 64 && failed & (1 << (ISOLATE_BIND_SHIFT + index)) != 0 {
+            error!("[CONTAINER] Failed to bind shared folder {:?}", source);
+        }
+    }
+}
+
+/// PID of the container init process, if it is still alive
+///
+/// Until init is reaped its PID cannot be reused, so the value is safe to
+/// signal while the child is held.
+pub fn init_pid() -> Option<i32> {
+    let mut init = INIT_PROCESS.lock().unwrap();
+    if let Some(status) = reap_init(&mut init) {
+        drop(init);
+        report_exit(status);
+        return None;
+    }
+    init.as_ref().map(|child| child.id() as i32)
+}
+
+/// Whether the container init process is still alive
+pub fn is_running() -> bool {
+    init_pid().is_some()
+}
+
+/// How long the container has been running, `None` if it is not
+pub fn uptime() -> Option<Duration> {
+    if !is_running() {
+        return None;
+    }
+    INIT_STARTED.lock().unwrap().map(|started| started.elapsed())
+}
+
+/// Record whether the guest has finished booting
+pub fn set_boot_completed(completed: bool) {
+    info!("[CONTAINER] Boot completed: {}", completed);
+    BOOT_COMPLETED.store(completed, Ordering::Release);
+}
+
+/// Whether the running container has finished booting
+pub fn boot_completed() -> bool {
+    is_running() && BOOT_COMPLETED.load(Ordering::Acquire)
+}
+
+/// Fields of `/proc/<pid>/stat` after the command name, starting with the state
+fn stat_fields(pid: i32) -> Option<Vec<String>> {
+    let stat = std::fs::read_to_string(format!("/proc/{}/stat", pid)).ok()?;
+    // The command name may contain spaces, the fields after it do not.
+    let rest = &stat[stat.rfind(')')? + 1..];
+    Some(rest.split_whitespace().map(String::from).collect())
+}
+
+/// Parent PID of a process, read from `/proc/<pid>/stat`
+fn parent_pid(pid: i32) -> Option<i32> {
+    stat_fields(pid)?.get(1)?.parse().ok()
+}
+
+/// Start time of a process in clock ticks since boot
+///
+/// Together with the PID this names one process, even after the PID is reused.
+fn start_time(pid: i32) -> Option<u64> {
+    stat_fields(pid)?.get(19)?.parse().ok()
+}
+
+/// The init process followed by all of its descendants, parents first
+pub fn process_tree() -> Vec<i32> {
+    let root = match init_pid() {
+        Some(pid) => pid,
+        None => return Vec::new(),
+    };
+
+    let mut parents: Vec<(i32, i32)> = Vec::new();
+    if let Ok(entries) = std::fs::read_dir("/proc") {
+        for entry in entries.flatten() {
+            if let Some(pid) = entry.file_name().to_str().and_then(|n| n.parse::<i32>().ok()) {
+                if let Some(ppid) = parent_pid(pid) {
+                    parents.push((pid, ppid));
+                }
+            }
+        }
+    }
+
+    let mut tree = vec![root];
+    let mut index = 0;
+    while index < tree.len() {
+        let current = tree[index];
+        for &(pid, ppid) in &parents {
+            if ppid == current && !tree.contains(&pid) {
+                tree.push(pid);
+            }
+        }
+        index += 1;
+    }
+    tree
+}
+
+/// Send a signal to every process in the container
+fn signal_tree(signal: libc::c_int) -> io::Result<usize> {
+    let tree = process_tree();
+    if tree.is_empty() {
+        return Err(io::Error::new(io::ErrorKind::NotFound, "container is not running"));
+    }
+
+    for pid in &tree {
+        if unsafe { libc::kill(*pid, signal) } != 0 {
+            warn!("[CONTAINER] Failed to signal pid {}: {}", pid, io::Error::last_os_error());
+        }
+    }
+    Ok(tree.len())
+}
+
+/// Fail with NotFound unless init is alive
+///
+/// Checked before signalling so a container whose init has exited is never
+/// mistaken for whatever now holds its PID.
+fn ensure_running() -> io::Result<()> {
+    if is_running() {
+        Ok(())
+    } else {
+        Err(io::Error::new(io::ErrorKind::NotFound, "container is not running"))
+    }
+}
+
+/// Pause every process in the container, keeping its state
+pub fn freeze() -> io::Result<()> {
+    ensure_running()?;
+    // Stopping the parents first keeps new children from appearing mid-walk;
+    // a second pass catches anything forked before its parent stopped.
+    signal_tree(libc::SIGSTOP)?;
+    let count = signal_tree(libc::SIGSTOP)?;
+    info!("[CONTAINER] Froze {} container processes", count);
+    Ok(())
+}
+
+/// Resume a container paused by `freeze`
+pub fn thaw() -> io::Result<()> {
+    ensure_running()?;
+    let count = signal_tree(libc::SIGCONT)?;
+    info!("[CONTAINER] Thawed {} container processes", count);
+    Ok(())
+}
+
+/// Stop every process in the container and reap init
+///
+/// Processes get SIGTERM and `STOP_TIMEOUT` to exit before the remaining
+/// ones are killed. The input and renderer sockets belong to listeners in
+/// this process and are kept, so a restarted container reconnects to them.
+pub fn stop() -> io::Result<()> {
+    let tree: Vec<(i32, Option<u64>)> = process_tree().into_iter().map(|pid| (pid, start_time(pid))).collect();
+    if tree.is_empty() {
+        return Err(io::Error::new(io::ErrorKind::NotFound, "container is not running"));
+    }
+
+    STOPPING.store(true, Ordering::Release);
+    let result = stop_tree(&tree);
+    STOPPING.store(false, Ordering::Release);
+    result
+}
+
+fn stop_tree(tree: &[(i32, Option<u64>)]) -> io::Result<()> {
+    // A frozen container cannot act on SIGTERM
+    let _ = signal_tree(libc::SIGCONT);
+    let _ = signal_tree(libc::SIGTERM);
+
+    let deadline = Instant::now() + STOP_TIMEOUT;
+    while is_running() && Instant::now() < deadline {
+        thread::sleep(Duration::from_millis(100));
+    }
+
+    // Descendants may outlive init, so kill what is left of the original
+    // tree. A PID only still names the same process if its start time is
+    // unchanged; anything else is an unrelated process that reused it.
+    let mut killed = 0;
+    for (pid, started) in tree.iter().rev() {
+        if started.is_none() || start_time(*pid) != *started {
+            continue;
+        }
+        if unsafe { libc::kill(*pid, libc::SIGKILL) } == 0 {
+            killed += 1;
+        }
+    }
+    if killed > 0 {
+        warn!("[CONTAINER] Killed {} processes that ignored SIGTERM", killed);
+    }
+
+    if let Some(mut child) = INIT_PROCESS.lock().unwrap().take() {
+        let status = child.wait()?;
+        info!("[CONTAINER] Container init exited: {}", status);
+    }
+    Ok(())
+}
+
+/// Stop the container if it is running and start it again with the same loader
+pub fn restart() -> io::Result<()> {
+    let loader_path = LOADER_PATH.lock().unwrap().clone().ok_or_else(|| {
+        io::Error::new(io::ErrorKind::NotFound, "container was never started")
+    })?;
+
+    match stop() {
+        Ok(()) => {}
+        Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+        Err(e) => return Err(e),
+    }
+    start_init(&loader_path)
+}
@@ -9,21 +9,28 @@
 // License, v. 2.0. If a copy of the MPL was not distributed with this
 // file, You can obtain one at https://mozilla.org/MPL/2.0/.
 
-use log::info;
+use log::{error, info};
 use std::ffi::c_void;
-use std::fs::File;
-use std::process::{Command, Stdio};
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Mutex;
 use std::thread;
+use std::time::Duration;
 use once_cell::sync::Lazy;
 
+use crate::console_log;
+use crate::container;
+use crate::crash_dumps;
+use crate::events;
 use crate::input;
 use crate::renderer_bindings;
 use crate::renderer_new;
+use crate::seccomp;
 
 static RENDERER_STARTED: AtomicBool = AtomicBool::new(false);
 
+/// Set once the input system and container have been started, they outlive the renderer
+static SYSTEM_STARTED: AtomicBool = AtomicBool::new(false);
+
 /// Renderer type selection
 #[derive(Debug, Clone, Copy, PartialEq)]
 pub enum RendererType {
@@ -34,14 +41,28 @@ pub enum RendererType {
 /// Global renderer type setting
 static RENDERER_TYPE: Lazy<Mutex<RendererType>> = Lazy::new(|| Mutex::new(RendererType::Old));
 
+/// Renderer that actually started this session, which differs from
+/// `RENDERER_TYPE` after a fallback
+static ACTIVE_RENDERER: Lazy<Mutex<Option<RendererType>>> = Lazy::new(|| Mutex::new(None));
+
+/// The renderer window updates go to: the one that started, else the setting
+fn active_renderer() -> RendererType {
+    let active = *ACTIVE_RENDERER.lock().unwrap();
+    active.unwrap_or_else(|| *RENDERER_TYPE.lock().unwrap())
+}
+
 /// Global debug renderer setting
 static DEBUG_RENDERER: AtomicBool = AtomicBool::new(false);
 
 /// Global debug log directory
 static DEBUG_LOG_DIR: Lazy<Mutex<String>> = Lazy::new(|| Mutex::new(String::new()));
 
-/// Set the renderer type to use
+/// Set the renderer type to use, takes effect the next time the renderer starts
 pub fn set_renderer_type(use_new_renderer: bool) {
+    if RENDERER_STARTED.load(Ordering::Acquire) {
+        info!("[CORE] Renderer running, keeping {:?}", active_renderer());
+        return;
+    }
     let mut renderer_type = RENDERER_TYPE.lock().unwrap();
     *renderer_type = if use_new_renderer {
         RendererType::New
@@ -53,6 +74,28 @@ pub fn set_renderer_type(use_new_renderer: bool) {
     info!("[CORE] ========================================");
 }
 
+/// Select the renderer by backend name, takes effect the next time the renderer starts
+///
+/// "emugl" is the embedded libOpenglRender.so and "new" the open-source
+/// renderer, which falls back to emugl at start when no QEMU pipe is found.
+/// gfxstream and the software gralloc path are not part of this build and
+/// are refused. Returns false if the backend was not selected.
+pub fn set_renderer_backend(backend: &str) -> bool {
+    match backend {
+        "emugl" => set_renderer_type(false),
+        "new" => set_renderer_type(true),
+        "gfxstream" | "software" => {
+            error!("[CORE] Renderer backend {} is not available in this build", backend);
+            return false;
+        }
+        _ => {
+            error!("[CORE] Unknown renderer backend: {}", backend);
+            return false;
+        }
+    }
+    true
+}
+
 /// Set the debug renderer mode
 pub fn set_debug_renderer(debug_enabled: bool) {
     DEBUG_RENDERER.store(debug_enabled, Ordering::Relaxed);
@@ -76,6 +119,46 @@ pub fn set_debug_log_dir(log_dir: String) {
     renderer_new::set_debug_log_dir(log_dir);
 }
 
+/// Set the hostname the container sees when namespaces are available
+pub fn set_container_hostname(hostname: String) {
+    container::set_hostname(hostname);
+}
+
+/// Bind-mount a host directory into the container when it next starts,
+/// returns false if it cannot be shared
+pub fn add_shared_folder(host_dir: String, container_dir: String) -> bool {
+    match container::add_shared_folder(host_dir, container_dir) {
+        Ok(()) => true,
+        Err(e) => {
+            error!("[CORE] Cannot share folder: {}", e);
+            false
+        }
+    }
+}
+
+/// Propagate the host timezone and locale into the container
+pub fn set_host_time_settings(timezone: String, locale: String) {
+    container::set_host_time_settings(timezone, locale);
+}
+
+/// Configure rotation of the container console log
+pub fn set_log_rotation(max_kb: i32, max_minutes: i32, keep: i32, compress: bool) {
+    console_log::set_rotation(
+        max_kb.max(0) as u64 * 1024,
+        Duration::from_secs(max_minutes.max(0) as u64 * 60),
+        keep.max(0) as usize,
+        compress,
+    );
+}
+
+/// Set the seccomp profile installed on the container
+pub fn set_seccomp_profile(profile: i32) {
+    match seccomp::SeccompProfile::from_int(profile) {
+        Some(profile) => seccomp::set_profile(profile),
+        None => error!("[CORE] Unknown seccomp profile: {}", profile),
+    }
+}
+
 /// Initialize the renderer with the given parameters
 pub fn init_renderer(
     window: *mut c_void,
@@ -93,8 +176,8 @@ pub fn init_renderer(
     info!("[CORE] Surface: {}x{}, Virtual: {}x{}, FPS: {}", 
           surface_width, surface_height, virtual_width, virtual_height, fps);
 
-    let renderer_type = *RENDERER_TYPE.lock().unwrap();
-    info!("[CORE] Using renderer: {:?}", renderer_type);
+    let mut renderer_type = *RENDERER_TYPE.lock().unwrap();
+    info!("[CORE] Requested renderer: {:?}", renderer_type);
     info!("[CORE] ========================================");
 
     if RENDERER_STARTED
@@ -102,6 +185,7 @@ pub fn init_renderer(
         .is_err()
     {
         info!("[CORE] Renderer already started, updating window");
+        renderer_type = active_renderer();
         // Renderer already started, just update window
         match renderer_type {
             RendererType::Old => {
@@ -138,9 +222,21 @@ pub fn init_renderer(
             }
         }
     } else {
-        info!("[CORE] First time initialization");
-        // First time initialization
-        input::start_input_system(virtual_width, virtual_height);
+        renderer_type = probe_renderer(renderer_type);
+        info!("[CORE] Using renderer: {:?}", renderer_type);
+
+        info!("[CORE] Starting renderer");
+        if !SYSTEM_STARTED.load(Ordering::Acquire) {
+            // Refuse to start on a rootfs another live process is driving
+            if let Err(e) = container::acquire_rootfs() {
+                error!("[CORE] Cannot take ownership of rootfs: {}", e);
+                RENDERER_STARTED.store(false, Ordering::Release);
+                return;
+            }
+
+            // First time initialization
+            input::start_input_system(virtual_width, virtual_height);
+        }
 
         // Convert raw pointer to usize for safe transfer between threads
         let window_addr = window as usize;
@@ -162,24 +258,61 @@ pub fn init_renderer(
                     let result = renderer_new::start_renderer(window, virtual_width, virtual_height, xdpi, ydpi, fps);
                     if result != 0 {
                         info!("[CORE] New renderer failed to start (result={}), this is expected if QEMU pipe is not available", result);
+                        // Let the next surface try again
+                        RENDERER_STARTED.store(false, Ordering::Release);
+                        events::emit(events::RENDERER_ERROR, &result.to_string());
                     }
                 }
             }
         });
 
-        let working_dir = "/data/data/io.twoyi/rootfs";
-        let log_path = "/data/data/io.twoyi/log.txt";
-        info!("[CORE] Starting container init process");
-        info!("[CORE] Working directory: {}", working_dir);
-        info!("[CORE] Log path: {}", log_path);
-        let outputs = File::create(log_path).unwrap();
-        let errors = outputs.try_clone().unwrap();
-        let _ = Command::new("./init")
-            .current_dir(working_dir)
-            .env("TYLOADER", loader_path)
-            .stdout(Stdio::from(outputs))
-            .stderr(Stdio::from(errors))
-            .spawn();
+        if !SYSTEM_STARTED.swap(true, Ordering::AcqRel) {
+            info!("[CORE] Starting container init process");
+            if let Err(e) = container::start_init(&loader_path) {
+                error!("[CORE] Failed to start container init: {}", e);
+            }
+            crash_dumps::start_watching();
+        }
+    }
+}
+
+/// Fall back to the old renderer when the new one cannot run here
+///
+/// The choice is kept for this session only, so window updates go to the
+/// renderer that started while the setting is left as the user chose it.
+fn probe_renderer(requested: RendererType) -> RendererType {
+    let chosen = match requested {
+        RendererType::New if !renderer_new::pipe::is_pipe_available() => {
+            info!("[CORE] QEMU pipe not available, falling back to old renderer");
+            RendererType::Old
+        }
+        _ => requested,
+    };
+    *ACTIVE_RENDERER.lock().unwrap() = Some(chosen);
+    chosen
+}
+
+/// Tear down the renderer so the next `init_renderer` starts it on a fresh surface
+///
+/// The container and input devices keep running.
+pub fn destroy_renderer() {
+    if !RENDERER_STARTED.load(Ordering::Acquire) {
+        info!("[CORE] Renderer not started, nothing to destroy");
+        return;
+    }
+
+    let renderer_type = active_renderer();
+    info!("[CORE] Destroying {:?} renderer", renderer_type);
+    match renderer_type {
+        RendererType::Old => unsafe {
+            // The library's render thread lives on and takes the next
+            // surface through setNativeWindow, so keep it marked started.
+            renderer_bindings::destroyOpenGLSubwindow();
+        },
+        RendererType::New => {
+            renderer_new::destroy_subwindow();
+            RENDERER_STARTED.store(false, Ordering::Release);
+        }
     }
 }
 
@@ -193,7 +326,7 @@ pub fn reset_window(
     fb_width: i32,
     fb_height: i32,
 ) {
-    let renderer_type = *RENDERER_TYPE.lock().unwrap();
+    let renderer_type = active_renderer();
     
     match renderer_type {
         RendererType::Old => unsafe {
@@ -227,7 +360,7 @@ pub fn reset_window(
 
 /// Remove a window
 pub fn remove_window(window: *mut c_void) {
-    let renderer_type = *RENDERER_TYPE.lock().unwrap();
+    let renderer_type = active_renderer();
     
     match renderer_type {
         RendererType::Old => unsafe {
@@ -238,3 +371,102 @@ pub fn remove_window(window: *mut c_void) {
         }
     }
 }
+
+/// Pause the container while keeping its state
+pub fn freeze_container() {
+    if let Err(e) = container::freeze() {
+        error!("[CORE] Failed to freeze container: {}", e);
+    }
+}
+
+/// Resume a frozen container
+pub fn thaw_container() {
+    if let Err(e) = container::thaw() {
+        error!("[CORE] Failed to thaw container: {}", e);
+    }
+}
+
+/// Stop the container, returns false if it could not be stopped
+pub fn stop_container() -> bool {
+    info!("[CORE] Stopping container");
+    match container::stop() {
+        Ok(()) => true,
+        Err(e) => {
+            error!("[CORE] Failed to stop container: {}", e);
+            false
+        }
+    }
+}
+
+/// Stop the container if running and start it again
+pub fn restart_container() -> bool {
+    info!("[CORE] Restarting container");
+    match container::restart() {
+        Ok(()) => true,
+        Err(e) => {
+            error!("[CORE] Failed to restart container: {}", e);
+            false
+        }
+    }
+}
+
+/// Cheap liveness check, returned as a JSON object
+///
+/// `renderer` describes the open-source renderer when it is in use; the
+/// original libOpenglRender.so exposes no state, so it is null there.
+pub fn health_report() -> String {
+    let container_alive = container::is_running();
+    let renderer_started = RENDERER_STARTED.load(Ordering::Relaxed);
+    let renderer_type = active_renderer();
+    let touch_connected = input::touch_connected();
+    let key_connected = input::key_connected();
+
+    let status = match renderer_type {
+        RendererType::New => renderer_new::status(),
+        RendererType::Old => None,
+    };
+    let renderer_ready = renderer_type == RendererType::Old || status.is_some();
+    let renderer = match status {
+        Some(status) => format!(
+            "{{\"width\":{},\"height\":{},\"gralloc\":{},\"frames\":{}}}",
+            status.width, status.height, status.gralloc, status.frames
+        ),
+        None => String::from("null"),
+    };
+    let healthy = container_alive && renderer_started && renderer_ready && touch_connected && key_connected;
+
+    format!(
+        "{{\"healthy\":{},\"container_alive\":{},\"renderer_started\":{},\"renderer_type\":\"{:?}\",\"renderer\":{},\"touch_connected\":{},\"key_connected\":{}}}",
+        healthy, container_alive, renderer_started, renderer_type, renderer, touch_connected, key_connected
+    )
+}
+
+/// Container supervision state, returned as a JSON object
+pub fn container_status() -> String {
+    let running = container::is_running();
+    let pid = match container::init_pid() {
+        Some(pid) if running => pid.to_string(),
+        _ => String::from("null"),
+    };
+    let uptime_ms = match container::uptime() {
+        Some(uptime) => uptime.as_millis().to_string(),
+        None => String::from("null"),
+    };
+    let boot_completed = container::boot_completed();
+    let renderer_started = RENDERER_STARTED.load(Ordering::Relaxed);
+    // Only the open-source renderer counts frames
+    let frames = match renderer_new::status() {
+        Some(status) if active_renderer() == RendererType::New => status.frames.to_string(),
+        _ => String::from("null"),
+    };
+
+    format!(
+        "{{\"running\":{},\"pid\":{},\"uptime_ms\":{},\"boot_completed\":{},\"renderer_started\":{},\"frames\":{}}}",
+        running, pid, uptime_ms, boot_completed, renderer_started, frames
+    )
+}
+
+/// Record whether the guest has finished booting, reported by `container_status`
+pub fn set_boot_completed(completed: bool) {
+    container::set_boot_completed(completed);
+}
@@ -0,0 +1,170 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+//! Collection of crash artifacts written inside the container
+//!
+//! The ROM's debuggerd writes tombstones and the ANR traces go to
+//! `data/anr`, both inside the rootfs where the app UI cannot offer them.
+//! New files are copied to a host-side directory and announced as events.
+
+use log::{info, warn};
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::container::ROOTFS_PATH;
+use crate::events;
+
+/// Host-side directory the artifacts are copied to
+pub const CRASH_DIR: &str = "/data/data/io.twoyi/crashes";
+
+/// Directories watched inside the rootfs, with the prefix given to their copies
+const WATCHED: &[(&str, &str)] = &[("data/tombstones", "tombstone"), ("data/anr", "anr")];
+
+const SCAN_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Files modified more recently than this may still be being written
+const SETTLE_TIME: Duration = Duration::from_secs(2);
+
+static WATCHING: AtomicBool = AtomicBool::new(false);
+
+/// Start the watcher thread, once
+pub fn start_watching() {
+    if WATCHING.swap(true, Ordering::AcqRel) {
+        return;
+    }
+    thread::spawn(|| {
+        if let Err(e) = fs::create_dir_all(CRASH_DIR) {
+            warn!("[CRASH] Cannot create {}: {}", CRASH_DIR, e);
+        }
+        // Artifacts from earlier runs are not news
+        let mut seen = HashMap::new();
+        scan(&mut seen, false);
+        loop {
+            thread::sleep(SCAN_INTERVAL);
+            scan(&mut seen, true);
+        }
+    });
+}
+
+/// Look for new or rewritten files, copying them out when `report` is set
+///
+/// Tombstone names are reused round-robin, so files are told apart by
+/// modification time as well as name.
+fn scan(seen: &mut HashMap<PathBuf, SystemTime>, report: bool) {
+    for (dir, prefix) in WATCHED {
+        let entries = match fs::read_dir(Path::new(ROOTFS_PATH).join(dir)) {
+            Ok(entries) => entries,
+            Err(_) => continue,
+        };
+        for entry in entries.flatten() {
+            let path = entry.path();
+            let modified = match entry.metadata().and_then(|m| m.modified()) {
+                Ok(modified) if entry.file_type().map(|t| t.is_file()).unwrap_or(false) => modified,
+                _ => continue,
+            };
+            if seen.get(&path) == Some(&modified) {
+                continue;
+            }
+            if report && modified.elapsed().map(|age| age < SETTLE_TIME).unwrap_or(true) {
+                continue;
+            }
+            seen.insert(path.clone(), modified);
+            if report {
+                collect(&path, prefix, modified);
+            }
+        }
+    }
+}
+
+fn collect(path: &Path, prefix: &str, modified: SystemTime) {
+    let stamp = modified.duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0);
+    let name = path.file_name().map(|n| n.to_string_lossy().into_owned()).unwrap_or_default();
+    let target = Path::new(CRASH_DIR).join(format!("{}-{}-{}", prefix, stamp, name));
+
+    if let Err(e) = fs::copy(path, &target) {
+        warn!("[CRASH] Failed to copy {}: {}", path.display(), e);
+        return;
+    }
+    let process = crashed_process(path).unwrap_or_default();
+    info!("[CRASH] {} crashed, saved {}", process, target.display());
+
+    events::emit(
+        events::APP_CRASHED,
+        &format!(
+            "{{\"process\":\"{}\",\"path\":\"{}\"}}",
+            json_escape(&process),
+            json_escape(&target.to_string_lossy())
+        ),
+    );
+}
+
+/// Process named in a tombstone (`>>> name <<<`) or ANR trace (`Cmd line: name`)
+fn crashed_process(path: &Path) -> Option<String> {
+    let content = fs::read(path).ok()?;
+    let content = String::from_utf8_lossy(&content);
+    content.lines().take(64).find_map(|line| {
+        if let (Some(start), Some(end)) = (line.find(">>> "), line.find(" <<<")) {
+            if start + 4 <= end {
+                return Some(line[start + 4..end].to_string());
+            }
+        }
+        line.strip_prefix("Cmd line: ").map(|name| name.trim().to_string())
+    })
+}
+
+fn json_escape(s: &str) -> String {
+    let mut escaped = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            c if (c as u32) < 0x20 => escaped.push_str(&format!("\\u{:04x}", c as u32)),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_json_escape() {
+        assert_eq!(json_escape("com.example"), "com.example");
+        assert_eq!(json_escape("a\"b\\c"), "a\\\"b\\\\c");
+        assert_eq!(json_escape("line\nnext\t"), "line\\u000anext\\u0009");
+        assert_eq!(json_escape("héllo"), "héllo");
+    }
+
+    #[test]
+    fn test_crashed_process() {
+        let dir = std::env::temp_dir().join(format!("twoyi-crash-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+
+        let tombstone = dir.join("tombstone_00");
+        fs::write(
+            &tombstone,
+            "*** *** ***\nBuild fingerprint: 'x'\npid: 1234, tid: 1234, name: main  >>> com.example.app <<<\n",
+        )
+        .unwrap();
+        assert_eq!(crashed_process(&tombstone).as_deref(), Some("com.example.app"));
+
+        let anr = dir.join("anr_trace");
+        fs::write(&anr, "----- pid 99 at 2026-01-01 -----\nCmd line: system_server \n").unwrap();
+        assert_eq!(crashed_process(&anr).as_deref(), Some("system_server"));
+
+        let other = dir.join("other");
+        fs::write(&other, "<<< reversed >>>\nnothing here\n").unwrap();
+        assert_eq!(crashed_process(&other), None);
+        assert_eq!(crashed_process(&dir.join("missing")), None);
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,92 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+//! Callbacks from native code into the app
+//!
+//! The app registers one listener object through `Renderer.setEventListener`
+//! and is told about things it would otherwise have to poll for. Events are
+//! delivered on whichever native thread noticed them, attached to the VM as
+//! a daemon.
+
+use jni::objects::{GlobalRef, JObject, JValue};
+use jni::signature::{JavaType, Primitive};
+use jni::JavaVM;
+use log::{error, info};
+use once_cell::sync::{Lazy, OnceCell};
+use std::sync::Mutex;
+
+use crate::jni_cache;
+
+/// The container's init process exited, detail is its exit status
+pub const CONTAINER_EXITED: &str = "container_exited";
+/// The renderer failed to start, detail is the renderer's result code
+pub const RENDERER_ERROR: &str = "renderer_error";
+/// A tombstone or ANR trace appeared, detail is JSON with "process" and the copy's "path"
+pub const APP_CRASHED: &str = "app_crashed";
+
+static JVM: OnceCell<JavaVM> = OnceCell::new();
+
+static LISTENER: Lazy<Mutex<Option<GlobalRef>>> = Lazy::new(|| Mutex::new(None));
+
+/// Keep the VM handed to `JNI_OnLoad` so native threads can call back into Java
+pub fn set_java_vm(jvm: &JavaVM) {
+    match unsafe { JavaVM::from_raw(jvm.get_java_vm_pointer()) } {
+        Ok(jvm) => {
+            let _ = JVM.set(jvm);
+        }
+        Err(e) => error!("[EVENTS] Failed to keep JavaVM: {}", e),
+    }
+}
+
+/// Replace the listener, `None` stops delivery
+pub fn set_listener(listener: Option<GlobalRef>) {
+    *LISTENER.lock().unwrap() = listener;
+}
+
+/// Deliver an event to the listener, if one is registered
+pub fn emit(event: &str, detail: &str) {
+    info!("[EVENTS] {}: {}", event, detail);
+
+    let listener = match LISTENER.lock().unwrap().clone() {
+        Some(listener) => listener,
+        None => return,
+    };
+    let jvm = match JVM.get() {
+        Some(jvm) => jvm,
+        None => return,
+    };
+    let env = match jvm.attach_current_thread_as_daemon() {
+        Ok(env) => env,
+        Err(e) => {
+            error!("[EVENTS] Failed to attach thread: {}", e);
+            return;
+        }
+    };
+
+    let result = (|| {
+        let event = env.new_string(event)?;
+        let detail = env.new_string(detail)?;
+        let args = [JValue::Object(JObject::from(event)), JValue::Object(JObject::from(detail))];
+        match jni_cache::event_listener_on_event() {
+            Some(method) => env.call_method_unchecked(
+                listener.as_obj(),
+                method,
+                JavaType::Primitive(Primitive::Void),
+                &args,
+            )?,
+            None => env.call_method(
+                listener.as_obj(),
+                "onEvent",
+                "(Ljava/lang/String;Ljava/lang/String;)V",
+                &args,
+            )?,
+        };
+        Ok::<(), jni::errors::Error>(())
+    })();
+    if let Err(e) = result {
+        error!("[EVENTS] Failed to deliver {}: {}", event, e);
+        // Don't leave an exception pending on a thread that never returns to Java
+        let _ = env.exception_clear();
+    }
+}
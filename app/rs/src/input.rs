@@ -7,15 +7,23 @@ use libc::{c_char, c_int};
 use ndk::event::{MotionAction, MotionEvent};
 use std::mem;
 use std::thread;
-use std::{io::Write};
+use std::time::{Duration, Instant};
+use std::io::{Read, Write};
 use uinput_sys::*;
 
-use std::sync::mpsc::{ channel, Sender};
-use std::sync::Mutex;
+use std::cell::{Cell, RefCell};
+use std::net::Shutdown;
+use std::sync::mpsc::{ channel, Receiver, Sender};
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard};
 use once_cell::sync::Lazy;
 
 use log::info;
 
+use crate::input_latency;
+use crate::input_record;
+use crate::keymap;
+
 const FF_MAX: u16 = 0x7f;
 
 const TOUCH_PATH: &'static str = "/data/data/io.twoyi/rootfs/dev/input/touch";
@@ -26,6 +34,22 @@ const KEY_DEVICE_NAME: &'static str = "vkey";
 const KEY_DEVICE_UNIQUE_ID: &'static str = "<keyboard 0>";
 const KEY_PATH: &'static str = "/data/data/io.twoyi/rootfs/dev/input/key0";
 
+const KEYBOARD_DEVICE_NAME: &'static str = "vkeyboard";
+const KEYBOARD_DEVICE_UNIQUE_ID: &'static str = "<keyboard 1>";
+const KEYBOARD_PATH: &'static str = "/data/data/io.twoyi/rootfs/dev/input/keyboard0";
+
+const GAMEPAD_DEVICE_NAME: &'static str = "vgamepad";
+const GAMEPAD_DEVICE_UNIQUE_ID: &'static str = "<gamepad 0>";
+const GAMEPAD_PATH: &'static str = "/data/data/io.twoyi/rootfs/dev/input/gamepad0";
+
+const MOUSE_DEVICE_NAME: &'static str = "vmouse";
+const MOUSE_DEVICE_UNIQUE_ID: &'static str = "<mouse 0>";
+const MOUSE_PATH: &'static str = "/data/data/io.twoyi/rootfs/dev/input/mouse0";
+
+const STYLUS_DEVICE_NAME: &'static str = "vstylus";
+const STYLUS_DEVICE_UNIQUE_ID: &'static str = "<stylus 0>";
+const STYLUS_PATH: &'static str = "/data/data/io.twoyi/rootfs/dev/input/stylus0";
+
 #[repr(C)]
 #[derive(Clone, Copy)]
 struct device_info {
@@ -45,6 +69,44 @@ struct device_info {
     abs_min: [u32; ABS_CNT as usize],
 }
 
+impl device_info {
+    /// Serialize to the layout the guest reads: the C struct layout of an
+    /// LP64 little-endian target, padding included and zeroed
+    ///
+    /// Written field by field so the bytes on the wire no longer depend on how
+    /// the host compiler lays out the struct. The guest reader ships in the
+    /// rootfs and expects exactly this layout, so it carries no version.
+    fn to_bytes(self) -> Vec<u8> {
+        fn pad_to(out: &mut Vec<u8>, align: usize) {
+            while !out.len().is_multiple_of(align) {
+                out.push(0);
+            }
+        }
+
+        let mut out = Vec::with_capacity(mem::size_of::<device_info>());
+        out.extend(self.name.iter().map(|&c| c as u8));
+        out.extend_from_slice(&self.driver_version.to_le_bytes());
+        for field in [self.id.bustype, self.id.vendor, self.id.product, self.id.version] {
+            out.extend_from_slice(&field.to_le_bytes());
+        }
+        out.extend(self.physical_location.iter().map(|&c| c as u8));
+        out.extend(self.unique_id.iter().map(|&c| c as u8));
+        out.extend_from_slice(&self.key_bitmask);
+        out.extend_from_slice(&self.abs_bitmask);
+        out.extend_from_slice(&self.rel_bitmask);
+        out.extend_from_slice(&self.sw_bitmask);
+        out.extend_from_slice(&self.led_bitmask);
+        out.extend_from_slice(&self.ff_bitmask);
+        out.extend_from_slice(&self.prop_bitmask);
+        pad_to(&mut out, 4);
+        for value in self.abs_max.iter().chain(self.abs_min.iter()) {
+            out.extend_from_slice(&value.to_le_bytes());
+        }
+        pad_to(&mut out, 4);
+        out
+    }
+}
+
 unsafe fn any_as_u8_slice<T: Sized>(p: &T) -> &[u8] {
     ::std::slice::from_raw_parts((p as *const T) as *const u8, ::std::mem::size_of::<T>())
 }
@@ -59,10 +121,32 @@ fn copy_to_cstr<const COUNT: usize>(data: &str, arr: &mut [u8; COUNT]) {
     arr[..len].copy_from_slice(bytes);
 }
 
-const MAX_POINTERS: usize = 5;
+/// Default number of multi-touch slots
+const DEFAULT_TOUCH_SLOTS: usize = 10;
+
+/// Upper bound for the configurable slot count
+const MAX_TOUCH_SLOTS: usize = 32;
+
+static TOUCH_SLOTS: AtomicUsize = AtomicUsize::new(DEFAULT_TOUCH_SLOTS);
 
-static INPUT_SENDER: Lazy<Mutex<Option<Sender<input_event>>>> = Lazy::new(|| { Mutex::new(None)});
-static KEY_SENDER: Lazy<Mutex<Option<Sender<input_event>>>> = Lazy::new(|| { Mutex::new(None)});
+/// Per-slot touch state, sized when the touch device is created
+static G_INPUT_MT: Lazy<Mutex<Vec<i32>>> = Lazy::new(|| Mutex::new(Vec::new()));
+
+static INPUT_SENDER: Lazy<Mutex<Option<FrameSender>>> = Lazy::new(|| { Mutex::new(None)});
+static KEY_SENDER: Lazy<Mutex<Option<FrameSender>>> = Lazy::new(|| { Mutex::new(None)});
+static KEYBOARD_SENDER: Lazy<Mutex<Option<FrameSender>>> = Lazy::new(|| { Mutex::new(None)});
+
+/// The attached keyboard listener's enable flag, `None` while detached
+///
+/// Attach and detach hold this lock throughout, so a detach can't unlink the
+/// socket of an attach that is racing it.
+static KEYBOARD: Lazy<Mutex<Option<Arc<AtomicBool>>>> = Lazy::new(|| Mutex::new(None));
+static GAMEPAD_SENDER: Lazy<Mutex<Option<FrameSender>>> = Lazy::new(|| { Mutex::new(None)});
+static MOUSE_SENDER: Lazy<Mutex<Option<FrameSender>>> = Lazy::new(|| { Mutex::new(None)});
+static STYLUS_SENDER: Lazy<Mutex<Option<FrameSender>>> = Lazy::new(|| { Mutex::new(None)});
+
+/// Mouse buttons currently held, as a `MOUSE_BUTTONS` bitmask
+static MOUSE_BUTTON_STATE: Lazy<Mutex<i32>> = Lazy::new(|| Mutex::new(0));
 
 pub fn start_input_system(width: i32, height: i32) {
     thread::spawn(move || {
@@ -71,10 +155,153 @@ pub fn start_input_system(width: i32, height: i32) {
     thread::spawn(|| {
         key_server();
     });
+    thread::spawn(|| {
+        gamepad_server();
+    });
+    thread::spawn(|| {
+        mouse_server();
+    });
+    thread::spawn(move || {
+        stylus_server(width, height);
+    });
+}
+
+/// Set the number of multi-touch slots, takes effect when the input system starts
+pub fn set_touch_slots(slots: i32) {
+    let slots = (slots.max(1) as usize).min(MAX_TOUCH_SLOTS);
+    TOUCH_SLOTS.store(slots, Ordering::Relaxed);
+}
+
+/// Whether any finger is down on the touch device
+pub fn touch_active() -> bool {
+    G_INPUT_MT.lock().unwrap().iter().any(|&slot| slot != 0)
+}
+
+/// Whether the guest has connected to the touch device
+pub fn touch_connected() -> bool {
+    INPUT_SENDER.lock().unwrap().is_some()
+}
+
+/// Whether the guest has connected to the key device
+pub fn key_connected() -> bool {
+    KEY_SENDER.lock().unwrap().is_some()
+}
+
+/// Offer the full keyboard device to the guest, while a physical keyboard is in use
+///
+/// A device with letter keys makes Android treat it as a hardware keyboard
+/// and hide the soft keyboard, so it only exists while one is attached.
+pub fn attach_keyboard() {
+    let mut keyboard = KEYBOARD.lock().unwrap();
+    if keyboard.is_some() {
+        return;
+    }
+    info!("attaching full keyboard device");
+    let _ = std::fs::remove_file(KEYBOARD_PATH);
+    let listener = match unix_socket::UnixListener::bind(KEYBOARD_PATH) {
+        Ok(listener) => listener,
+        Err(e) => {
+            info!("keyboard bind failed: {}", e);
+            return;
+        }
+    };
+    let active = Arc::new(AtomicBool::new(true));
+    *keyboard = Some(active.clone());
+    thread::spawn(move || {
+        let device = generate_keyboard_device();
+        serve_device("keyboard", listener, device, &KEYBOARD_SENDER, Some(active));
+    });
+}
+
+/// Withdraw the full keyboard device; keys go to the key device again
+pub fn detach_keyboard() {
+    let mut keyboard = KEYBOARD.lock().unwrap();
+    let active = match keyboard.take() {
+        Some(active) => active,
+        None => return,
+    };
+    info!("detaching full keyboard device");
+    release_all_keys();
+    {
+        // Dropping the sender shuts down the guest's live connection; the
+        // listener checks the flag under the same lock before publishing one.
+        let mut sender = KEYBOARD_SENDER.lock().unwrap();
+        active.store(false, Ordering::Release);
+        *sender = None;
+    }
+
+    // Wake the listener so it sees the flag, then make the device disappear
+    let _ = unix_socket::UnixStream::connect(KEYBOARD_PATH);
+    let _ = std::fs::remove_file(KEYBOARD_PATH);
+}
+
+/// The device key events go to: the full keyboard when the guest has it, else the key device
+fn key_sender() -> MutexGuard<'static, Option<FrameSender>> {
+    let keyboard = KEYBOARD_SENDER.lock().unwrap();
+    if keyboard.is_some() {
+        return keyboard;
+    }
+    drop(keyboard);
+    KEY_SENDER.lock().unwrap()
+}
+
+/// Re-send a recorded event to the device it was recorded on
+pub fn replay_event(device: &str, kind: i32, code: i32, value: i32) {
+    let sender = match device {
+        "touch" => &INPUT_SENDER,
+        "key" => &KEY_SENDER,
+        "keyboard" => &KEYBOARD_SENDER,
+        "gamepad" => &GAMEPAD_SENDER,
+        "mouse" => &MOUSE_SENDER,
+        "stylus" => &STYLUS_SENDER,
+        _ => return,
+    };
+    if let Some(ref tx) = *sender.lock().unwrap() {
+        input_event_write(tx, kind, code, value);
+    }
+}
+
+/// Buffers events until SYN_REPORT, then hands the whole frame to the
+/// device's writer thread as one message
+///
+/// Only used while the device's sender lock is held, which is what makes the
+/// unsynchronized buffer sound.
+pub struct FrameSender {
+    device: &'static str,
+    tx: Sender<Frame>,
+    pending: RefCell<Vec<input_event>>,
+    started: Cell<Option<Instant>>,
+}
+
+/// A complete input frame and when its first event was submitted
+struct Frame {
+    events: Vec<input_event>,
+    started: Option<Instant>,
+}
+
+impl FrameSender {
+    fn new(device: &'static str, tx: Sender<Frame>) -> Self {
+        FrameSender { device, tx, pending: RefCell::new(Vec::new()), started: Cell::new(None) }
+    }
+
+    fn push(&self, ev: input_event) {
+        let mut pending = self.pending.borrow_mut();
+        if pending.is_empty() && input_latency::is_enabled() {
+            self.started.set(Some(Instant::now()));
+        }
+        input_record::record(self.device, &ev);
+        pending.push(ev);
+        if ev.kind as i32 == EV_SYN && ev.code as i32 == SYN_REPORT {
+            let _ = self.tx.send(Frame {
+                events: std::mem::take(&mut *pending),
+                started: self.started.take(),
+            });
+        }
+    }
 }
 
 pub fn input_event_write(
-    tx: &std::sync::mpsc::Sender<input_event>,
+    tx: &FrameSender,
     kind: i32,
     code: i32,
     val: i32,
@@ -92,7 +319,61 @@ pub fn input_event_write(
         value: val,
         time: tv,
     };
-    let _ = tx.send(ev);
+    tx.push(ev);
+}
+
+/// Swap the x and y axes, applied after offset and scale
+pub const TOUCH_SWAP_XY: i32 = 0x1;
+/// Mirror the x axis within the device range, applied after the swap
+pub const TOUCH_INVERT_X: i32 = 0x2;
+/// Mirror the y axis within the device range, applied after the swap
+pub const TOUCH_INVERT_Y: i32 = 0x4;
+
+/// Mapping from client coordinates to the touch device range
+#[derive(Debug, Clone, Copy)]
+struct TouchTransform {
+    offset_x: f32,
+    offset_y: f32,
+    scale_x: f32,
+    scale_y: f32,
+    flags: i32,
+}
+
+static TOUCH_TRANSFORM: Lazy<Mutex<TouchTransform>> = Lazy::new(|| {
+    Mutex::new(TouchTransform { offset_x: 0.0, offset_y: 0.0, scale_x: 1.0, scale_y: 1.0, flags: 0 })
+});
+
+/// Touch device range, set when the input system starts
+static TOUCH_RANGE: Lazy<Mutex<(i32, i32)>> = Lazy::new(|| Mutex::new((0, 0)));
+
+/// Set the client to device coordinate mapping
+///
+/// A point maps as `(p - offset) * scale`, then the `TOUCH_*` flags apply.
+/// Clients drawing a letterboxed or scaled view can send raw window
+/// coordinates by passing the view's origin and scale here.
+pub fn set_touch_transform(offset_x: f32, offset_y: f32, scale_x: f32, scale_y: f32, flags: i32) {
+    let transform = TouchTransform { offset_x, offset_y, scale_x, scale_y, flags };
+    info!("touch transform: {:?}", transform);
+    *TOUCH_TRANSFORM.lock().unwrap() = transform;
+}
+
+/// Map a client point into the touch device range, clamping to its edges
+fn map_touch(x: f32, y: f32) -> (i32, i32) {
+    let t = *TOUCH_TRANSFORM.lock().unwrap();
+    let (width, height) = *TOUCH_RANGE.lock().unwrap();
+
+    let mut x = (x - t.offset_x) * t.scale_x;
+    let mut y = (y - t.offset_y) * t.scale_y;
+    if t.flags & TOUCH_SWAP_XY != 0 {
+        std::mem::swap(&mut x, &mut y);
+    }
+    if t.flags & TOUCH_INVERT_X != 0 {
+        x = width as f32 - x;
+    }
+    if t.flags & TOUCH_INVERT_Y != 0 {
+        y = height as f32 - y;
+    }
+    ((x as i32).clamp(0, width), (y as i32).clamp(0, height))
 }
 
 pub fn handle_touch(ev: MotionEvent) {
@@ -107,43 +388,41 @@ pub fn handle_touch(ev: MotionEvent) {
 
         // info!("action: {:#?}, pointer_index: {}", action, pointer_index);
 
-        static G_INPUT_MT: Lazy<Mutex<[i32;MAX_POINTERS]>> = Lazy::new(|| {std::sync::Mutex::new([0i32;MAX_POINTERS])});
+        let slots = G_INPUT_MT.lock().unwrap().len();
+        // Moves carry every pointer and are filtered per pointer below
+        if action != MotionAction::Move && (pointer_id < 0 || pointer_id as usize >= slots) {
+            info!("pointer id {} out of range ({} slots), dropped", pointer_id, slots);
+            return;
+        }
 
         match action {
             MotionAction::Down | MotionAction::PointerDown => {
-                let x = pointer.x();
-                let y = pointer.y();
+                let (x, y) = map_touch(pointer.x(), pointer.y());
 
                 let mut mt = G_INPUT_MT.lock().unwrap();
                 mt[pointer_id as usize] = 1;
 
-                let mut index = 0;
-                while index < MAX_POINTERS {
-                    if mt[index] != 0 {
-                        input_event_write(fd, EV_ABS, ABS_MT_SLOT, pointer_id);
-                        input_event_write(fd, EV_ABS, ABS_MT_TRACKING_ID, pointer_id + 1);
+                input_event_write(fd, EV_ABS, ABS_MT_SLOT, pointer_id);
+                input_event_write(fd, EV_ABS, ABS_MT_TRACKING_ID, pointer_id + 1);
 
-                        if index == 0 {
-                            input_event_write(fd, EV_KEY, BTN_TOUCH, 108);
-                            input_event_write(fd, EV_KEY, BTN_TOOL_FINGER, 108);
-                        }
+                if action == MotionAction::Down {
+                    input_event_write(fd, EV_KEY, BTN_TOUCH, 108);
+                    input_event_write(fd, EV_KEY, BTN_TOOL_FINGER, 108);
+                }
 
-                        input_event_write(fd, EV_ABS, ABS_MT_POSITION_X, x as i32);
-                        input_event_write(fd, EV_ABS, ABS_MT_POSITION_Y, y as i32);
+                input_event_write(fd, EV_ABS, ABS_MT_POSITION_X, x);
+                input_event_write(fd, EV_ABS, ABS_MT_POSITION_Y, y);
 
-                        input_event_write(fd, EV_ABS, ABS_MT_PRESSURE, pressure as i32);
+                input_event_write(fd, EV_ABS, ABS_MT_PRESSURE, pressure as i32);
 
-                        input_event_write(fd, EV_SYN, SYN_REPORT, SYN_REPORT);
-                    }
-                    index = index + 1;
-                }
+                input_event_write(fd, EV_SYN, SYN_REPORT, SYN_REPORT);
             }
             MotionAction::Up => {
                 // let x = pointer.x();
                 // let y = pointer.y();
 
                 let mut index = 0;
-                while index != MAX_POINTERS {
+                while index != slots {
                     let mut mt = G_INPUT_MT.lock().unwrap();
                     if mt[index] != 0 {
                         mt[index] = 0;
@@ -155,26 +434,19 @@ pub fn handle_touch(ev: MotionEvent) {
                 }
             }
             MotionAction::Move => {
-                let mut index = 0;
+                let mt = G_INPUT_MT.lock().unwrap();
 
-                while index != MAX_POINTERS {
-                    let mt = G_INPUT_MT.lock().unwrap();
-                    if mt[index] != 0 {
-                        let x = pointer.x();
-                        let y = pointer.y();
-                        let pressure = pointer.pressure();
-
-                        input_event_write(fd, EV_ABS, ABS_MT_SLOT, index.try_into().unwrap());
-                        input_event_write(fd, EV_ABS, ABS_MT_POSITION_X, x as i32);
-                        input_event_write(fd, EV_ABS, ABS_MT_POSITION_Y, y as i32);
-
-                        input_event_write(fd, EV_ABS, ABS_MT_PRESSURE, pressure as i32);
-
-                        input_event_write(fd, EV_SYN, SYN_REPORT, SYN_REPORT);
-                    }
-
-                    index = index + 1;
+                // Batched samples first, oldest to newest, then the current one
+                for sample in ev.history() {
+                    let pointers = sample
+                        .pointers()
+                        .map(|p| (p.pointer_id(), p.x(), p.y(), p.pressure()));
+                    write_touch_sample(fd, &mt, pointers);
                 }
+                let pointers = ev
+                    .pointers()
+                    .map(|p| (p.pointer_id(), p.x(), p.y(), p.pressure()));
+                write_touch_sample(fd, &mt, pointers);
             }
             MotionAction::Cancel | MotionAction::PointerUp => {
                 // let x = pointer.x();
@@ -195,7 +467,30 @@ pub fn handle_touch(ev: MotionEvent) {
     }
 }
 
-fn generate_touch_device(width: i32, height: i32) -> device_info {
+/// Write one position update for every tracked pointer, closed by a single SYN_REPORT
+fn write_touch_sample<I>(fd: &FrameSender, mt: &[i32], pointers: I)
+where
+    I: Iterator<Item = (i32, f32, f32, f32)>,
+{
+    let mut written = false;
+    for (pointer_id, x, y, pressure) in pointers {
+        if pointer_id < 0 || pointer_id as usize >= mt.len() || mt[pointer_id as usize] == 0 {
+            continue;
+        }
+        let (x, y) = map_touch(x, y);
+
+        input_event_write(fd, EV_ABS, ABS_MT_SLOT, pointer_id);
+        input_event_write(fd, EV_ABS, ABS_MT_POSITION_X, x);
+        input_event_write(fd, EV_ABS, ABS_MT_POSITION_Y, y);
+        input_event_write(fd, EV_ABS, ABS_MT_PRESSURE, pressure as i32);
+        written = true;
+    }
+    if written {
+        input_event_write(fd, EV_SYN, SYN_REPORT, SYN_REPORT);
+    }
+}
+
+fn generate_touch_device(width: i32, height: i32, slots: usize) -> device_info {
     let iid = input_id {
         product: 0x1,
         version: 0,
@@ -239,7 +534,8 @@ fn generate_touch_device(width: i32, height: i32) -> device_info {
     info.abs_min[ABS_MT_TOUCH_MAJOR as usize] = 0;
     info.abs_min[ABS_MT_TOUCH_MINOR as usize] = 15;
 
-    info.abs_min[ABS_MT_SLOT as usize] = 4;
+    info.abs_min[ABS_MT_SLOT as usize] = 0;
+    info.abs_max[ABS_MT_SLOT as usize] = slots as u32 - 1;
     info.abs_min[ABS_MT_PRESSURE as usize] = 0;
     info.abs_max[ABS_MT_PRESSURE as usize] = 80;
 
@@ -247,37 +543,149 @@ fn generate_touch_device(width: i32, height: i32) -> device_info {
 }
 
 fn touch_server(width: i32, height: i32) {
-    let device = generate_touch_device(width, height);
-    let _ = std::fs::remove_file(TOUCH_PATH);
-    let listener = unix_socket::UnixListener::bind(TOUCH_PATH).unwrap();
+    let slots = TOUCH_SLOTS.load(Ordering::Relaxed);
+    *G_INPUT_MT.lock().unwrap() = vec![0; slots];
+    info!("touch device has {} slots", slots);
+
+    *TOUCH_RANGE.lock().unwrap() = (width, height);
+
+    let device = generate_touch_device(width, height, slots);
+    device_server("touch", TOUCH_PATH, device, &INPUT_SENDER);
+}
+
+/// Serve a virtual input device on a unix socket inside the rootfs
+///
+/// The guest reads the `device_info` header once per connection, followed by
+/// a stream of raw `input_event`s sent through `sender`.
+///
+/// The guest may reconnect at any time, e.g. after the container restarts.
+/// Each connection gets its own channel and `sender` always points at the
+/// newest one; a connection's writer thread exits when it is replaced or
+/// when the guest hangs up, and clears `sender` if it was still current.
+fn device_server(
+    name: &'static str,
+    path: &str,
+    device: device_info,
+    sender: &'static Lazy<Mutex<Option<FrameSender>>>,
+) {
+    let _ = std::fs::remove_file(path);
+    let listener = unix_socket::UnixListener::bind(path).unwrap();
+    serve_device(name, listener, device, sender, None);
+}
+
+/// Accept guest connections on an already bound `listener`
+///
+/// With an `active` flag the listener stops once the flag is cleared; the
+/// flag is checked under the `sender` lock so a connection is never
+/// published after its owner cleared both.
+fn serve_device(
+    name: &'static str,
+    listener: unix_socket::UnixListener,
+    device: device_info,
+    sender: &'static Lazy<Mutex<Option<FrameSender>>>,
+    active: Option<Arc<AtomicBool>>,
+) {
+    let current = Arc::new(AtomicU64::new(0));
+    let mut connection = 0u64;
+
     for stream in listener.incoming() {
         match stream {
             Ok(mut stream) => {
-                info!("touch client connected!");
+                connection += 1;
+                info!("{} client connected! (connection {})", name, connection);
 
-                let _ = stream.write_all(unsafe { any_as_u8_slice(&device) });
-
-                let (tx, rx) = channel::<input_event>();
-                *INPUT_SENDER.lock().unwrap() = Some(tx);
+                if let Err(e) = stream.write_all(&device.to_bytes()) {
+                    info!("{} handshake failed: {}", name, e);
+                    continue;
+                }
 
-                thread::spawn(move || loop {
-                    let ret = rx.recv();
-                    if let Ok(ev) = ret {
-                        let data = unsafe { any_as_u8_slice(&ev) };
-                        let _ = stream.write_all(data);
+                let (tx, rx) = channel::<Frame>();
+                {
+                    // Replacing the sender drops the previous connection's
+                    // channel, which ends its writer thread.
+                    let mut guard = sender.lock().unwrap();
+                    if active.as_ref().is_some_and(|active| !active.load(Ordering::Acquire)) {
+                        break;
                     }
-                });
+                    current.store(connection, Ordering::Release);
+                    *guard = Some(FrameSender::new(name, tx));
+                }
+
+                serve_connection(name, stream, rx, sender, current.clone(), connection);
             }
-            Err(_) => {
-                info!("touch server error happened!");
-                break;
+            Err(e) => {
+                info!("{} server error happened: {}", name, e);
+                thread::sleep(Duration::from_millis(100));
             }
         }
     }
 
-    info!("drop listener!");
+    info!("drop {} listener!", name);
 }
 
+/// Forward frames from `rx` to one guest connection until it is replaced or hangs up
+///
+/// The guest never writes, so a read returning EOF means it hung up. When the
+/// writer stops, for whatever reason, the socket is shut down: the guest sees
+/// EOF and the hangup watcher's read returns, so neither thread nor the fd
+/// outlives the connection.
+fn serve_connection(
+    name: &'static str,
+    mut stream: unix_socket::UnixStream,
+    rx: Receiver<Frame>,
+    sender: &'static Lazy<Mutex<Option<FrameSender>>>,
+    current: Arc<AtomicU64>,
+    id: u64,
+) {
+    match stream.try_clone() {
+        Ok(mut hangup) => {
+            let current = current.clone();
+            thread::spawn(move || {
+                let mut buf = [0u8; 64];
+                while let Ok(n) = hangup.read(&mut buf) {
+                    if n == 0 {
+                        break;
+                    }
+                }
+                let mut guard = sender.lock().unwrap();
+                if current.load(Ordering::Acquire) == id {
+                    info!("{} client hung up (connection {})", name, id);
+                    *guard = None;
+                }
+            });
+        }
+        Err(e) => info!("{} cannot watch for hangup: {}", name, e),
+    }
+
+    thread::spawn(move || {
+        let mut data = Vec::new();
+        for frame in rx {
+            // One write per frame instead of one per event
+            data.clear();
+            for ev in &frame.events {
+                data.extend_from_slice(unsafe { any_as_u8_slice(ev) });
+            }
+            let written = stream.write_all(&data);
+            if let Some(started) = frame.started {
+                input_latency::record(started.elapsed());
+            }
+            if let Err(e) = written {
+                info!("{} client disconnected (connection {}): {}", name, id, e);
+                let mut guard = sender.lock().unwrap();
+                if current.load(Ordering::Acquire) == id {
+                    *guard = None;
+                }
+                break;
+            }
+        }
+        let _ = stream.shutdown(Shutdown::Both);
+        info!("{} connection {} closed", name, id);
+    });
+}
+
+/// Keys above the BTN_* range that Android maps to system keys
+const EXTRA_KEYS: &[i32] = &[KEY_APPSELECT];
+
 fn generate_key_device() -> device_info {
     let mut info: device_info = unsafe { std::mem::MaybeUninit::zeroed().assume_init() };
 
@@ -288,45 +696,597 @@ fn generate_key_device() -> device_info {
     copy_to_cstr(KEY_PATH, &mut info.physical_location);
     copy_to_cstr(KEY_DEVICE_UNIQUE_ID, &mut info.unique_id);
 
+    // Volume and power only: a device without letter keys is not taken for
+    // a hardware keyboard. Other keys are still delivered through it.
     info.key_bitmask[14] = 0x1C;
 
     info
 }
 
-pub fn send_key_code(_keycode: i32) {
-    if let Some(ref tx) = *KEY_SENDER.lock().unwrap() {
-        input_event_write(tx, EV_KEY, KEY_BACK, 1);
-        input_event_write(tx, EV_SYN, SYN_REPORT, SYN_REPORT);
-        input_event_write(tx, EV_KEY, KEY_BACK, 0);
+fn generate_keyboard_device() -> device_info {
+    let mut info: device_info = unsafe { std::mem::MaybeUninit::zeroed().assume_init() };
+
+    info.driver_version = 0x1;
+    info.id.product = 0x1;
+
+    copy_to_cstr(KEYBOARD_DEVICE_NAME, &mut info.name);
+    copy_to_cstr(KEYBOARD_PATH, &mut info.physical_location);
+    copy_to_cstr(KEYBOARD_DEVICE_UNIQUE_ID, &mut info.unique_id);
+
+    // Every keyboard key; BTN_* codes are left to the pointer devices
+    for key in 1..BTN_MISC {
+        set_bit(&mut info.key_bitmask, key);
+    }
+    for &key in EXTRA_KEYS {
+        set_bit(&mut info.key_bitmask, key);
+    }
+
+    info
+}
+
+/// Android `KeyEvent` actions
+pub const KEY_ACTION_DOWN: i32 = 0;
+pub const KEY_ACTION_UP: i32 = 1;
+
+/// Tap a key given as an Android keycode
+pub fn send_key_code(keycode: i32) {
+    send_key_event(keycode, KEY_ACTION_DOWN, 0, 0);
+    send_key_event(keycode, KEY_ACTION_UP, 0, 0);
+}
+
+/// Forward an Android `KeyEvent`
+///
+/// Repeats are dropped since held keys already auto-repeat here; modifiers
+/// in `meta_state` are held around the key.
+pub fn send_key_event(keycode: i32, action: i32, meta_state: i32, repeat: i32) {
+    let key = match keymap::android_keycode_to_key(keycode) {
+        Some(key) => key,
+        None => {
+            info!("no key for android keycode {}", keycode);
+            return;
+        }
+    };
+    match action {
+        KEY_ACTION_DOWN if repeat == 0 => send_key_with_meta(key, true, meta_state),
+        KEY_ACTION_DOWN => {}
+        KEY_ACTION_UP => send_key_with_meta(key, false, meta_state),
+        _ => info!("unsupported key action {}", action),
+    }
+}
+
+/// Delay before a held key starts repeating, as the kernel's default
+const KEY_REPEAT_DELAY: Duration = Duration::from_millis(250);
+
+/// Interval between repeats of a held key
+const KEY_REPEAT_PERIOD: Duration = Duration::from_millis(33);
+
+/// Keys currently held down on the key device
+static HELD_KEYS: Lazy<Mutex<Vec<i32>>> = Lazy::new(|| Mutex::new(Vec::new()));
+
+/// Bumped on every key press, stale repeat threads exit on it
+static KEY_REPEAT_GENERATION: AtomicU64 = AtomicU64::new(0);
+
+/// Press or release a key, `key` is a linux KEY_* code
+///
+/// Like a real keyboard, the most recently pressed key auto-repeats until
+/// it is released.
+pub fn send_key(key: i32, pressed: bool) {
+    if !(1..BTN_MISC).contains(&key) && !EXTRA_KEYS.contains(&key) {
+        info!("unsupported key: {}", key);
+        return;
+    }
+
+    {
+        let mut held = HELD_KEYS.lock().unwrap();
+        let position = held.iter().position(|&k| k == key);
+        match (pressed, position) {
+            (true, None) => held.push(key),
+            (false, Some(index)) => {
+                held.remove(index);
+            }
+            // Already in the requested state
+            _ => return,
+        }
+    }
+
+    if let Some(ref tx) = *key_sender() {
+        input_event_write(tx, EV_KEY, key, pressed as i32);
+        input_event_write(tx, EV_SYN, SYN_REPORT, 0);
+    }
+
+    if pressed {
+        let generation = KEY_REPEAT_GENERATION.fetch_add(1, Ordering::AcqRel) + 1;
+        thread::spawn(move || repeat_key(key, generation));
+    }
+}
+
+/// Emit kernel-style repeat events (value 2) while `key` stays the repeating key
+fn repeat_key(key: i32, generation: u64) {
+    thread::sleep(KEY_REPEAT_DELAY);
+    loop {
+        if KEY_REPEAT_GENERATION.load(Ordering::Acquire) != generation
+            || HELD_KEYS.lock().unwrap().last() != Some(&key)
+        {
+            return;
+        }
+        if let Some(ref tx) = *key_sender() {
+            input_event_write(tx, EV_KEY, key, 2);
+            input_event_write(tx, EV_SYN, SYN_REPORT, 0);
+        }
+        thread::sleep(KEY_REPEAT_PERIOD);
+    }
+}
+
+/// Release every key still held, e.g. when the host window loses focus
+pub fn release_all_keys() {
+    let held: Vec<i32> = HELD_KEYS.lock().unwrap().clone();
+    for key in held.into_iter().rev() {
+        send_key(key, false);
+    }
+    // Modifiers pressed for a key were released above along with it
+    IMPLIED_MODIFIERS.lock().unwrap().clear();
+}
+
+/// Modifier bits of a meta state, same values as Android's `KeyEvent.META_*_ON`
+pub const META_SHIFT_ON: i32 = 0x1;
+pub const META_ALT_ON: i32 = 0x2;
+pub const META_CTRL_ON: i32 = 0x1000;
+pub const META_META_ON: i32 = 0x10000;
+
+/// Meta state bit, and the left and right keys that set it
+const MODIFIERS: &[(i32, i32, i32)] = &[
+    (META_SHIFT_ON, KEY_LEFTSHIFT, KEY_RIGHTSHIFT),
+    (META_CTRL_ON, KEY_LEFTCTRL, KEY_RIGHTCTRL),
+    (META_ALT_ON, KEY_LEFTALT, KEY_RIGHTALT),
+    (META_META_ON, KEY_LEFTMETA, KEY_RIGHTMETA),
+];
+
+/// A key and the modifiers pressed on its behalf
+type ImpliedModifiers = (i32, Vec<i32>);
+
+/// Modifiers pressed on behalf of a key, keyed by that key
+static IMPLIED_MODIFIERS: Lazy<Mutex<Vec<ImpliedModifiers>>> = Lazy::new(|| Mutex::new(Vec::new()));
+
+/// Current meta state, derived from the modifier keys held down
+pub fn current_meta_state() -> i32 {
+    let held = HELD_KEYS.lock().unwrap();
+    MODIFIERS
+        .iter()
+        .filter(|(_, left, right)| held.contains(left) || held.contains(right))
+        .fold(0, |state, (bit, _, _)| state | bit)
+}
+
+/// Press or release a key with the modifiers in `meta_state` held around it
+///
+/// Modifiers that are not already held are pressed before the key and
+/// released after it, so e.g. Ctrl+A can be sent as a single key event.
+pub fn send_key_with_meta(key: i32, pressed: bool, meta_state: i32) {
+    if pressed {
+        let current = current_meta_state();
+        let implied: Vec<i32> = MODIFIERS
+            .iter()
+            .filter(|(bit, _, _)| meta_state & bit != 0 && current & bit == 0)
+            .map(|&(_, left, _)| left)
+            .collect();
+        for &modifier in &implied {
+            send_key(modifier, true);
+        }
+        send_key(key, true);
+        if !implied.is_empty() {
+            IMPLIED_MODIFIERS.lock().unwrap().push((key, implied));
+        }
+    } else {
+        send_key(key, false);
+        let implied = {
+            let mut all = IMPLIED_MODIFIERS.lock().unwrap();
+            all.iter()
+                .position(|(k, _)| *k == key)
+                .map(|index| all.remove(index).1)
+        };
+        for modifier in implied.into_iter().flatten().rev() {
+            send_key(modifier, false);
+        }
+    }
+}
+
+/// Press or release a physical key, named as in the web `KeyboardEvent.code`
+pub fn send_code(code: &str, pressed: bool) {
+    match keymap::code_to_key(code) {
+        Some(key) => send_key(key, pressed),
+        None => info!("unknown key code: {}", code),
+    }
+}
+
+/// Press or release the key producing an X11 keysym in the container's layout
+///
+/// Shift is held around the key when the keysym is on the shift level.
+pub fn send_keysym(keysym: u32, pressed: bool) {
+    let (key, shift) = match keymap::keysym_to_key(keysym) {
+        Some(key) => key,
+        None => {
+            info!("no key for keysym {:#x}", keysym);
+            return;
+        }
+    };
+    let meta = if shift { META_SHIFT_ON } else { 0 };
+    send_key_with_meta(key, pressed, meta);
+}
+
+/// Set the keyboard layout used to map characters and keysyms to keys
+pub fn set_keyboard_layout(name: &str) {
+    match keymap::Layout::from_name(name) {
+        Some(layout) => keymap::set_layout(layout),
+        None => info!("unknown keyboard layout: {}", name),
+    }
+}
+
+/// Type a string as a sequence of key presses
+///
+/// Characters without a key in the configured layout are skipped. Returns
+/// how many characters were typed.
+pub fn type_text(text: &str) -> usize {
+    let mut typed = 0;
+    if let Some(ref tx) = *key_sender() {
+        for c in text.chars() {
+            let (key, shift) = match keymap::char_to_key(c) {
+                Some(key) => key,
+                None => {
+                    info!("cannot type character {:?}", c);
+                    continue;
+                }
+            };
+            if shift {
+                input_event_write(tx, EV_KEY, KEY_LEFTSHIFT, 1);
+            }
+            input_event_write(tx, EV_KEY, key, 1);
+            input_event_write(tx, EV_SYN, SYN_REPORT, 0);
+            input_event_write(tx, EV_KEY, key, 0);
+            if shift {
+                input_event_write(tx, EV_KEY, KEY_LEFTSHIFT, 0);
+            }
+            input_event_write(tx, EV_SYN, SYN_REPORT, 0);
+            typed += 1;
+        }
+    }
+    typed
+}
+
+/// Delete text around the cursor the way an IME would, with backspace and delete taps
+pub fn delete_surrounding_text(before: i32, after: i32) {
+    if let Some(ref tx) = *key_sender() {
+        for (key, count) in [(KEY_BACKSPACE, before), (KEY_DELETE, after)] {
+            for _ in 0..count.max(0) {
+                input_event_write(tx, EV_KEY, key, 1);
+                input_event_write(tx, EV_SYN, SYN_REPORT, 0);
+                input_event_write(tx, EV_KEY, key, 0);
+                input_event_write(tx, EV_SYN, SYN_REPORT, 0);
+            }
+        }
     }
 }
 
 fn key_server() {
     let device = generate_key_device();
-    let _ = std::fs::remove_file(KEY_PATH);
-    let listener = unix_socket::UnixListener::bind(KEY_PATH).unwrap();
-    for stream in listener.incoming() {
-        match stream {
-            Ok(mut stream) => {
-                info!("key client connected!");
+    device_server("key", KEY_PATH, device, &KEY_SENDER);
+}
 
-                let _ = stream.write_all(unsafe { any_as_u8_slice(&device) });
+fn set_bit(mask: &mut [u8], bit: i32) {
+    mask[bit as usize / 8] |= 1 << (bit % 8);
+}
 
-                let (tx, rx) = channel::<input_event>();
-                *KEY_SENDER.lock().unwrap() = Some(tx);
+const GAMEPAD_BUTTONS: &[i32] = &[
+    BTN_SOUTH, BTN_EAST, BTN_NORTH, BTN_WEST,
+    BTN_TL, BTN_TR, BTN_TL2, BTN_TR2,
+    BTN_SELECT, BTN_START, BTN_MODE, BTN_THUMBL, BTN_THUMBR,
+];
 
-                thread::spawn(move || loop {
-                    let ret = rx.recv();
-                    if let Ok(ev) = ret {
-                        let data = unsafe { any_as_u8_slice(&ev) };
-                        let _ = stream.write_all(data);
-                    }
-                });
+/// Analog sticks, full signed 16-bit range
+///
+/// Laid out the way Android's Generic.kl expects: the right stick on
+/// ABS_Z/ABS_RZ rather than ABS_RX/ABS_RY.
+const GAMEPAD_STICKS: &[i32] = &[ABS_X, ABS_Y, ABS_Z, ABS_RZ];
+
+/// Analog triggers, left on ABS_BRAKE and right on ABS_GAS
+const GAMEPAD_TRIGGERS: &[i32] = &[ABS_BRAKE, ABS_GAS];
+const GAMEPAD_TRIGGER_MAX: i32 = 255;
+
+/// D-pad, reported as a hat switch
+const GAMEPAD_HATS: &[i32] = &[ABS_HAT0X, ABS_HAT0Y];
+
+fn generate_gamepad_device() -> device_info {
+    let mut info: device_info = unsafe { std::mem::MaybeUninit::zeroed().assume_init() };
+
+    info.driver_version = 0x1;
+    info.id.product = 0x3;
+
+    copy_to_cstr(GAMEPAD_DEVICE_NAME, &mut info.name);
+    copy_to_cstr(GAMEPAD_PATH, &mut info.physical_location);
+    copy_to_cstr(GAMEPAD_DEVICE_UNIQUE_ID, &mut info.unique_id);
+
+    for &button in GAMEPAD_BUTTONS {
+        set_bit(&mut info.key_bitmask, button);
+    }
+
+    // abs_min/abs_max are read back as signed values by the guest
+    for &axis in GAMEPAD_STICKS {
+        set_bit(&mut info.abs_bitmask, axis);
+        info.abs_min[axis as usize] = i16::MIN as i32 as u32;
+        info.abs_max[axis as usize] = i16::MAX as u32;
+    }
+    for &axis in GAMEPAD_TRIGGERS {
+        set_bit(&mut info.abs_bitmask, axis);
+        info.abs_min[axis as usize] = 0;
+        info.abs_max[axis as usize] = GAMEPAD_TRIGGER_MAX as u32;
+    }
+    for &axis in GAMEPAD_HATS {
+        set_bit(&mut info.abs_bitmask, axis);
+        info.abs_min[axis as usize] = -1i32 as u32;
+        info.abs_max[axis as usize] = 1;
+    }
+
+    info
+}
+
+fn gamepad_server() {
+    let device = generate_gamepad_device();
+    device_server("gamepad", GAMEPAD_PATH, device, &GAMEPAD_SENDER);
+}
+
+/// Press or release a gamepad button, `button` is a linux BTN_* code
+pub fn send_gamepad_button(button: i32, pressed: bool) {
+    if !GAMEPAD_BUTTONS.contains(&button) {
+        info!("unsupported gamepad button: {}", button);
+        return;
+    }
+    if let Some(ref tx) = *GAMEPAD_SENDER.lock().unwrap() {
+        input_event_write(tx, EV_KEY, button, pressed as i32);
+        input_event_write(tx, EV_SYN, SYN_REPORT, 0);
+    }
+}
+
+/// Clamp `value` to the range advertised for `axis`, None if the axis is not advertised
+fn clamp_gamepad_axis(axis: i32, value: i32) -> Option<i32> {
+    if GAMEPAD_STICKS.contains(&axis) {
+        Some(value.clamp(i16::MIN as i32, i16::MAX as i32))
+    } else if GAMEPAD_TRIGGERS.contains(&axis) {
+        Some(value.clamp(0, GAMEPAD_TRIGGER_MAX))
+    } else if GAMEPAD_HATS.contains(&axis) {
+        Some(value.clamp(-1, 1))
+    } else {
+        info!("unsupported gamepad axis: {}", axis);
+        None
+    }
+}
+
+/// Move a gamepad axis, `axis` is a linux ABS_* code
+///
+/// Values are clamped to the range advertised for the axis.
+pub fn send_gamepad_axis(axis: i32, value: i32) {
+    send_gamepad_axes(&[(axis, value)]);
+}
+
+/// Move several gamepad axes at once, as (ABS_* code, value) pairs
+///
+/// All updates go out in a single frame so the guest sees them together.
+pub fn send_gamepad_axes(axes: &[(i32, i32)]) {
+    if let Some(ref tx) = *GAMEPAD_SENDER.lock().unwrap() {
+        let mut sent = false;
+        for &(axis, value) in axes {
+            if let Some(value) = clamp_gamepad_axis(axis, value) {
+                input_event_write(tx, EV_ABS, axis, value);
+                sent = true;
             }
-            Err(_) => {
-                info!("key server error happened!");
-                break;
+        }
+        if sent {
+            input_event_write(tx, EV_SYN, SYN_REPORT, 0);
+        }
+    }
+}
+
+/// Mouse buttons, bit `n` of a button mask maps to entry `n`
+const MOUSE_BUTTONS: &[i32] = &[BTN_LEFT, BTN_RIGHT, BTN_MIDDLE, BTN_SIDE, BTN_EXTRA];
+
+fn generate_mouse_device() -> device_info {
+    let mut info: device_info = unsafe { std::mem::MaybeUninit::zeroed().assume_init() };
+
+    info.driver_version = 0x1;
+    info.id.product = 0x4;
+
+    copy_to_cstr(MOUSE_DEVICE_NAME, &mut info.name);
+    copy_to_cstr(MOUSE_PATH, &mut info.physical_location);
+    copy_to_cstr(MOUSE_DEVICE_UNIQUE_ID, &mut info.unique_id);
+
+    for &button in MOUSE_BUTTONS {
+        set_bit(&mut info.key_bitmask, button);
+    }
+    for &axis in &[REL_X, REL_Y, REL_WHEEL, REL_HWHEEL] {
+        set_bit(&mut info.rel_bitmask, axis);
+    }
+
+    info
+}
+
+fn mouse_server() {
+    let device = generate_mouse_device();
+    device_server("mouse", MOUSE_PATH, device, &MOUSE_SENDER);
+}
+
+/// Report one mouse update as a single evdev frame
+///
+/// `dx`/`dy` are relative motion, `wheel`/`hwheel` are scroll detents, and
+/// `buttons` is the full set of held buttons; only changes are sent.
+pub fn send_mouse_event(dx: i32, dy: i32, wheel: i32, hwheel: i32, buttons: i32) {
+    if let Some(ref tx) = *MOUSE_SENDER.lock().unwrap() {
+        let mut state = MOUSE_BUTTON_STATE.lock().unwrap();
+        let changed = *state ^ buttons;
+        for (bit, &button) in MOUSE_BUTTONS.iter().enumerate() {
+            if changed & (1 << bit) != 0 {
+                input_event_write(tx, EV_KEY, button, (buttons >> bit) & 1);
             }
         }
+        *state = buttons;
+
+        if dx != 0 {
+            input_event_write(tx, EV_REL, REL_X, dx);
+        }
+        if dy != 0 {
+            input_event_write(tx, EV_REL, REL_Y, dy);
+        }
+        if wheel != 0 {
+            input_event_write(tx, EV_REL, REL_WHEEL, wheel);
+        }
+        if hwheel != 0 {
+            input_event_write(tx, EV_REL, REL_HWHEEL, hwheel);
+        }
+        input_event_write(tx, EV_SYN, SYN_REPORT, 0);
+    }
+}
+
+/// Scroll by whole wheel detents, positive `dy` is up and positive `dx` is right
+///
+/// Sent through the virtual mouse so the guest delivers ACTION_SCROLL at the
+/// pointer, which lists handle natively. A device with only wheel axes would
+/// be classified as a rotary encoder instead, which most apps ignore.
+pub fn send_scroll(dx: i32, dy: i32) {
+    if dx == 0 && dy == 0 {
+        return;
+    }
+    let buttons = *MOUSE_BUTTON_STATE.lock().unwrap();
+    send_mouse_event(0, 0, dy, dx, buttons);
+}
+
+/// Pressure resolution of the stylus
+pub const STYLUS_PRESSURE_MAX: i32 = 4096;
+
+/// Tilt range of the stylus, in degrees from vertical
+const STYLUS_TILT_MAX: i32 = 90;
+
+/// Stylus is out of range of the screen
+pub const STYLUS_AWAY: i32 = 0;
+/// Stylus is hovering above the screen
+pub const STYLUS_HOVER: i32 = 1;
+/// Stylus is touching the screen
+pub const STYLUS_CONTACT: i32 = 2;
+
+fn generate_stylus_device(width: i32, height: i32) -> device_info {
+    let mut info: device_info = unsafe { std::mem::MaybeUninit::zeroed().assume_init() };
+
+    info.driver_version = 0x1;
+    info.id.product = 0x5;
+
+    copy_to_cstr(STYLUS_DEVICE_NAME, &mut info.name);
+    copy_to_cstr(STYLUS_PATH, &mut info.physical_location);
+    copy_to_cstr(STYLUS_DEVICE_UNIQUE_ID, &mut info.unique_id);
+
+    set_bit(&mut info.prop_bitmask, INPUT_PROP_DIRECT);
+
+    for &button in &[BTN_TOOL_PEN, BTN_TOUCH, BTN_STYLUS] {
+        set_bit(&mut info.key_bitmask, button);
+    }
+
+    let ranges = [
+        (ABS_X, 0, width),
+        (ABS_Y, 0, height),
+        (ABS_PRESSURE, 0, STYLUS_PRESSURE_MAX),
+        (ABS_TILT_X, -STYLUS_TILT_MAX, STYLUS_TILT_MAX),
+        (ABS_TILT_Y, -STYLUS_TILT_MAX, STYLUS_TILT_MAX),
+    ];
+    for (axis, min, max) in ranges {
+        set_bit(&mut info.abs_bitmask, axis);
+        info.abs_min[axis as usize] = min as u32;
+        info.abs_max[axis as usize] = max as u32;
+    }
+
+    info
+}
+
+fn stylus_server(width: i32, height: i32) {
+    let device = generate_stylus_device(width, height);
+    device_server("stylus", STYLUS_PATH, device, &STYLUS_SENDER);
+}
+
+/// Report the stylus position as one evdev frame
+///
+/// `state` is one of `STYLUS_AWAY`, `STYLUS_HOVER` or `STYLUS_CONTACT`;
+/// position, pressure and tilt are ignored once the stylus is away.
+pub fn send_stylus_event(x: i32, y: i32, pressure: i32, tilt_x: i32, tilt_y: i32, state: i32, button: bool) {
+    let pressure = match state {
+        STYLUS_AWAY => None,
+        STYLUS_HOVER => Some(0),
+        STYLUS_CONTACT => Some(pressure.clamp(1, STYLUS_PRESSURE_MAX)),
+        _ => {
+            info!("invalid stylus state: {}", state);
+            return;
+        }
+    };
+    if let Some(ref tx) = *STYLUS_SENDER.lock().unwrap() {
+        if let Some(pressure) = pressure {
+            input_event_write(tx, EV_ABS, ABS_X, x);
+            input_event_write(tx, EV_ABS, ABS_Y, y);
+            input_event_write(tx, EV_ABS, ABS_PRESSURE, pressure);
+            input_event_write(tx, EV_ABS, ABS_TILT_X, tilt_x.clamp(-STYLUS_TILT_MAX, STYLUS_TILT_MAX));
+            input_event_write(tx, EV_ABS, ABS_TILT_Y, tilt_y.clamp(-STYLUS_TILT_MAX, STYLUS_TILT_MAX));
+        }
+        input_event_write(tx, EV_KEY, BTN_TOOL_PEN, (state != STYLUS_AWAY) as i32);
+        input_event_write(tx, EV_KEY, BTN_TOUCH, (state == STYLUS_CONTACT) as i32);
+        input_event_write(tx, EV_KEY, BTN_STYLUS, (button && state != STYLUS_AWAY) as i32);
+        input_event_write(tx, EV_SYN, SYN_REPORT, 0);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_device_info_layout() {
+        let mut info: device_info = unsafe { std::mem::MaybeUninit::zeroed().assume_init() };
+        info.driver_version = 0x01020304;
+        info.id.bustype = 0x0506;
+        info.id.version = 0x0708;
+        copy_to_cstr("loc", &mut info.physical_location);
+        copy_to_cstr("uid", &mut info.unique_id);
+        info.key_bitmask[0] = 0xAA;
+        info.prop_bitmask[3] = 0xBB;
+        info.abs_max[0] = 0x11223344;
+        info.abs_min[info.abs_min.len() - 1] = 0x55667788;
+
+        let bytes = info.to_bytes();
+        assert_eq!(bytes.len(), 896);
+        assert_eq!(&bytes[80..84], &[0x04, 0x03, 0x02, 0x01]);
+        assert_eq!(&bytes[84..86], &[0x06, 0x05]);
+        assert_eq!(&bytes[90..92], &[0x08, 0x07]);
+        assert_eq!(&bytes[92..96], b"loc\0");
+        assert_eq!(&bytes[172..176], b"uid\0");
+        assert_eq!(bytes[252], 0xAA);
+        assert_eq!(bytes[381], 0xBB);
+        assert_eq!(&bytes[382..384], &[0, 0]);
+        assert_eq!(&bytes[384..388], &[0x44, 0x33, 0x22, 0x11]);
+        assert_eq!(&bytes[892..896], &[0x88, 0x77, 0x66, 0x55]);
+    }
+
+    #[test]
+    fn test_connection_closed_when_sender_dropped() {
+        static SENDER: Lazy<Mutex<Option<FrameSender>>> = Lazy::new(|| Mutex::new(None));
+
+        let (server, mut peer) = unix_socket::UnixStream::pair().unwrap();
+        peer.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+        let (tx, rx) = channel::<Frame>();
+        let current = Arc::new(AtomicU64::new(1));
+        *SENDER.lock().unwrap() = Some(FrameSender::new("test", tx));
+        serve_connection("test", server, rx, &SENDER, current, 1);
+
+        {
+            let guard = SENDER.lock().unwrap();
+            let tx = guard.as_ref().unwrap();
+            input_event_write(tx, EV_KEY, KEY_A, 1);
+            input_event_write(tx, EV_SYN, SYN_REPORT, 0);
+        }
+        let mut frame = [0u8; 2 * std::mem::size_of::<input_event>()];
+        peer.read_exact(&mut frame).unwrap();
+
+        *SENDER.lock().unwrap() = None;
+        let mut buf = [0u8; 16];
+        assert_eq!(peer.read(&mut buf).unwrap(), 0);
     }
 }
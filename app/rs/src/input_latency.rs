@@ -0,0 +1,109 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+//! Input latency instrumentation
+//!
+//! When enabled, every input frame is timed from the first event entering
+//! the input API to the end of its socket write, and the most recent samples
+//! are kept for a percentile report.
+
+use log::info;
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+use std::time::Duration;
+use once_cell::sync::Lazy;
+
+/// Number of recent samples the report is computed over
+const MAX_SAMPLES: usize = 1024;
+
+static ENABLED: AtomicBool = AtomicBool::new(false);
+
+static SAMPLES: Lazy<Mutex<VecDeque<Duration>>> = Lazy::new(|| Mutex::new(VecDeque::with_capacity(MAX_SAMPLES)));
+
+/// Turn measurement on or off, clearing previous samples
+pub fn set_enabled(enabled: bool) {
+    info!("[INPUT] Latency measurement: {}", enabled);
+    SAMPLES.lock().unwrap().clear();
+    ENABLED.store(enabled, Ordering::Release);
+}
+
+pub fn is_enabled() -> bool {
+    ENABLED.load(Ordering::Acquire)
+}
+
+/// Record the latency of one frame
+pub fn record(latency: Duration) {
+    if !is_enabled() {
+        return;
+    }
+    let mut samples = SAMPLES.lock().unwrap();
+    if samples.len() == MAX_SAMPLES {
+        samples.pop_front();
+    }
+    samples.push_back(latency);
+}
+
+/// Nearest-rank `p`th percentile of sorted samples in microseconds, 0 if there are none
+fn percentile(sorted: &[Duration], p: usize) -> u128 {
+    if sorted.is_empty() {
+        return 0;
+    }
+    let index = (sorted.len() * p / 100).min(sorted.len() - 1);
+    sorted[index].as_micros()
+}
+
+/// Percentiles of the recent samples in microseconds, as a JSON object
+pub fn report() -> String {
+    let mut sorted: Vec<Duration> = SAMPLES.lock().unwrap().iter().copied().collect();
+    sorted.sort();
+
+    format!(
+        "{{\"enabled\":{},\"samples\":{},\"p50_us\":{},\"p90_us\":{},\"p99_us\":{},\"max_us\":{}}}",
+        is_enabled(),
+        sorted.len(),
+        percentile(&sorted, 50),
+        percentile(&sorted, 90),
+        percentile(&sorted, 99),
+        sorted.last().map_or(0, |d| d.as_micros())
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_percentile() {
+        assert_eq!(percentile(&[], 50), 0);
+
+        let one = [Duration::from_micros(7)];
+        assert_eq!(percentile(&one, 50), 7);
+        assert_eq!(percentile(&one, 99), 7);
+
+        let sorted: Vec<Duration> = (1..=100).map(Duration::from_micros).collect();
+        assert_eq!(percentile(&sorted, 0), 1);
+        assert_eq!(percentile(&sorted, 50), 51);
+        assert_eq!(percentile(&sorted, 90), 91);
+        assert_eq!(percentile(&sorted, 99), 100);
+        assert_eq!(percentile(&sorted, 100), 100);
+    }
+
+    // The samples are global, so one test covers recording and the report
+    #[test]
+    fn test_report() {
+        set_enabled(false);
+        record(Duration::from_micros(5));
+        assert!(report().contains("\"samples\":0"));
+
+        set_enabled(true);
+        for micros in 1..=(MAX_SAMPLES as u64 + 10) {
+            record(Duration::from_micros(micros));
+        }
+        let report = report();
+        assert!(report.contains(&format!("\"samples\":{}", MAX_SAMPLES)), "{}", report);
+        assert!(report.contains(&format!("\"max_us\":{}", MAX_SAMPLES + 10)), "{}", report);
+        set_enabled(false);
+    }
+}
@@ -0,0 +1,178 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+//! Input macro recording and replay
+//!
+//! Records the exact event stream forwarded to each virtual input device,
+//! with its timing, so a UI interaction can be replayed later.
+
+use log::{info, warn};
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+use std::thread;
+use std::time::{Duration, Instant};
+use once_cell::sync::Lazy;
+use uinput_sys::input_event;
+
+use crate::input;
+
+/// Replay speed limits, outside them playback is either frozen or instant
+const MIN_SPEED: f32 = 0.1;
+const MAX_SPEED: f32 = 10.0;
+
+/// One forwarded event, relative to the start of its recording
+#[derive(Clone)]
+struct RecordedEvent {
+    device: &'static str,
+    offset: Duration,
+    kind: i32,
+    code: i32,
+    value: i32,
+}
+
+/// A recording in progress
+struct Recording {
+    started: Instant,
+    events: Vec<RecordedEvent>,
+}
+
+/// Finished recordings by id
+#[derive(Default)]
+struct Recordings {
+    next_id: usize,
+    finished: HashMap<usize, Vec<RecordedEvent>>,
+}
+
+/// Fast path for the input paths, mirrors `ACTIVE.is_some()`
+static RECORDING: AtomicBool = AtomicBool::new(false);
+
+static ACTIVE: Lazy<Mutex<Option<Recording>>> = Lazy::new(|| Mutex::new(None));
+
+static RECORDINGS: Lazy<Mutex<Recordings>> = Lazy::new(|| Mutex::new(Recordings::default()));
+
+/// Start recording, discarding a recording already in progress
+pub fn start() {
+    *ACTIVE.lock().unwrap() = Some(Recording { started: Instant::now(), events: Vec::new() });
+    RECORDING.store(true, Ordering::Release);
+    info!("[INPUT] Recording started");
+}
+
+/// Stop recording and keep the result, returns its id
+pub fn stop() -> Option<usize> {
+    RECORDING.store(false, Ordering::Release);
+    let recording = ACTIVE.lock().unwrap().take()?;
+
+    let mut recordings = RECORDINGS.lock().unwrap();
+    let id = recordings.next_id;
+    recordings.next_id += 1;
+    info!("[INPUT] Recording {} stopped with {} events", id, recording.events.len());
+    recordings.finished.insert(id, recording.events);
+    Some(id)
+}
+
+/// Forget a finished recording, false if there is none with that id
+pub fn delete(id: usize) -> bool {
+    RECORDINGS.lock().unwrap().finished.remove(&id).is_some()
+}
+
+/// Forget every finished recording; ids are not reused
+pub fn clear() {
+    RECORDINGS.lock().unwrap().finished.clear();
+}
+
+/// Called for every event submitted to `device`
+pub fn record(device: &'static str, ev: &input_event) {
+    if !RECORDING.load(Ordering::Acquire) {
+        return;
+    }
+    if let Some(ref mut recording) = *ACTIVE.lock().unwrap() {
+        recording.events.push(RecordedEvent {
+            device,
+            offset: recording.started.elapsed(),
+            kind: ev.kind as i32,
+            code: ev.code as i32,
+            value: ev.value,
+        });
+    }
+}
+
+/// Replay a recording on a background thread
+///
+/// `speed` scales playback, 2.0 replays twice as fast, and is clamped to
+/// `MIN_SPEED..=MAX_SPEED`. A recording with touch events is refused while
+/// fingers are down, its slots would collide with the live ones.
+pub fn replay(id: usize, speed: f32) -> bool {
+    let events = match RECORDINGS.lock().unwrap().finished.get(&id) {
+        Some(events) => events.clone(),
+        None => {
+            warn!("[INPUT] No recording with id {}", id);
+            return false;
+        }
+    };
+    if events.iter().any(|event| event.device == "touch") && input::touch_active() {
+        warn!("[INPUT] Not replaying recording {} while touches are active", id);
+        return false;
+    }
+    let speed = if speed.is_finite() && speed > 0.0 { speed.clamp(MIN_SPEED, MAX_SPEED) } else { 1.0 };
+
+    thread::spawn(move || {
+        info!("[INPUT] Replaying recording {} at {}x", id, speed);
+        play(&events, speed, |event| {
+            input::replay_event(event.device, event.kind, event.code, event.value)
+        });
+        info!("[INPUT] Replay of recording {} finished", id);
+    });
+    true
+}
+
+/// Hand each event to `sink` at its recorded offset, scaled by `speed`
+fn play(events: &[RecordedEvent], speed: f32, mut sink: impl FnMut(&RecordedEvent)) {
+    let start = Instant::now();
+    for event in events {
+        let due = event.offset.div_f32(speed);
+        let elapsed = start.elapsed();
+        if due > elapsed {
+            thread::sleep(due - elapsed);
+        }
+        sink(event);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn event(kind: i32, code: i32, value: i32) -> input_event {
+        let mut ev: input_event = unsafe { std::mem::zeroed() };
+        ev.kind = kind as u16;
+        ev.code = code as u16;
+        ev.value = value;
+        ev
+    }
+
+    #[test]
+    fn test_record_stop_replay() {
+        start();
+        record("record-test", &event(1, 30, 1));
+        record("record-test", &event(0, 0, 0));
+        record("record-test", &event(1, 30, 0));
+        let id = stop().unwrap();
+        record("record-test", &event(1, 31, 1));
+
+        let events: Vec<RecordedEvent> = RECORDINGS.lock().unwrap().finished[&id]
+            .iter()
+            .filter(|event| event.device == "record-test")
+            .cloned()
+            .collect();
+        let mut replayed = Vec::new();
+        play(&events, MAX_SPEED, |event| replayed.push((event.kind, event.code, event.value)));
+        assert_eq!(replayed, vec![(1, 30, 1), (0, 0, 0), (1, 30, 0)]);
+
+        assert!(replay(id, 1e-30));
+        assert!(delete(id));
+        assert!(!delete(id));
+        assert!(!replay(id, 1.0));
+    }
+}
@@ -0,0 +1,60 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+//! JNI IDs resolved once at `JNI_OnLoad`
+//!
+//! Looking fields and methods up by name searches the class on every
+//! call, which adds up on the touch path. The classes are pinned with
+//! global refs so the cached IDs stay valid for the life of the library.
+
+use jni::objects::{GlobalRef, JFieldID, JMethodID};
+use jni::sys::{jfieldID, jmethodID};
+use jni::JNIEnv;
+use log::info;
+use once_cell::sync::OnceCell;
+
+struct JniCache {
+    _motion_event_class: GlobalRef,
+    motion_event_native_ptr: jfieldID,
+    _event_listener_class: GlobalRef,
+    event_listener_on_event: jmethodID,
+}
+
+// IDs are plain handles, valid on every thread while their class is loaded
+unsafe impl Send for JniCache {}
+unsafe impl Sync for JniCache {}
+
+static CACHE: OnceCell<JniCache> = OnceCell::new();
+
+/// Resolve and pin everything the native entry points use
+pub fn init(env: &JNIEnv) -> jni::errors::Result<()> {
+    let motion_event = env.find_class("android/view/MotionEvent")?;
+    let motion_event_native_ptr = env.get_field_id(motion_event, "mNativePtr", "J")?.into_inner();
+    let motion_event_class = env.new_global_ref(motion_event)?;
+
+    let event_listener = env.find_class("io/twoyi/Renderer$EventListener")?;
+    let event_listener_on_event = env
+        .get_method_id(event_listener, "onEvent", "(Ljava/lang/String;Ljava/lang/String;)V")?
+        .into_inner();
+    let event_listener_class = env.new_global_ref(event_listener)?;
+
+    let _ = CACHE.set(JniCache {
+        _motion_event_class: motion_event_class,
+        motion_event_native_ptr,
+        _event_listener_class: event_listener_class,
+        event_listener_on_event,
+    });
+    info!("JNI cache initialized");
+    Ok(())
+}
+
+/// `MotionEvent.mNativePtr`
+pub fn motion_event_native_ptr() -> Option<JFieldID<'static>> {
+    CACHE.get().map(|cache| JFieldID::from(cache.motion_event_native_ptr))
+}
+
+/// `Renderer.EventListener.onEvent(String, String)`
+pub fn event_listener_on_event() -> Option<JMethodID<'static>> {
+    CACHE.get().map(|cache| JMethodID::from(cache.event_listener_on_event))
+}
@@ -0,0 +1,398 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+//! Client key identifiers to linux keycode translation
+//!
+//! Physical key codes (web `KeyboardEvent.code`) name a key position and map
+//! straight to a keycode. Characters and X11 keysyms name what a key types,
+//! which depends on the keyboard layout configured inside the container, so
+//! they are looked up in the table for that layout. Only characters on the
+//! base and shift levels can be produced; AltGr and dead keys are not.
+//!
+//! Android keycodes, as forwarded by the app, are mapped back to the linux
+//! keycodes the ROM's `Generic.kl` turns into them.
+
+use std::sync::Mutex;
+use once_cell::sync::Lazy;
+use uinput_sys::*;
+
+/// Keyboard layout the container interprets keycodes with
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Layout {
+    /// US QWERTY
+    Us,
+    /// French AZERTY
+    French,
+    /// German QWERTZ
+    German,
+}
+
+impl Layout {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_ascii_lowercase().as_str() {
+            "us" | "qwerty" => Some(Layout::Us),
+            "fr" | "azerty" => Some(Layout::French),
+            "de" | "qwertz" => Some(Layout::German),
+            _ => None,
+        }
+    }
+
+    /// Keycode, base character and shifted character of each printable key;
+    /// `'\0'` marks a level that does not type a plain character
+    fn table(self) -> &'static [(i32, char, char)] {
+        match self {
+            Layout::Us => US_KEYS,
+            Layout::French => FRENCH_KEYS,
+            Layout::German => GERMAN_KEYS,
+        }
+    }
+
+    /// Keycode and whether shift must be held to produce `c` with this layout
+    fn char_to_key(self, c: char) -> Option<(i32, bool)> {
+        match c {
+            ' ' => return Some((KEY_SPACE, false)),
+            '\n' => return Some((KEY_ENTER, false)),
+            '\t' => return Some((KEY_TAB, false)),
+            '\0' => return None,
+            _ => {}
+        }
+
+        self.table().iter().find_map(|&(key, base, shifted)| {
+            if c == base {
+                Some((key, false))
+            } else if c == shifted {
+                Some((key, true))
+            } else {
+                None
+            }
+        })
+    }
+}
+
+const US_KEYS: &[(i32, char, char)] = &[
+    (KEY_GRAVE, '`', '~'), (KEY_1, '1', '!'), (KEY_2, '2', '@'), (KEY_3, '3', '#'),
+    (KEY_4, '4', '$'), (KEY_5, '5', '%'), (KEY_6, '6', '^'), (KEY_7, '7', '&'),
+    (KEY_8, '8', '*'), (KEY_9, '9', '('), (KEY_0, '0', ')'), (KEY_MINUS, '-', '_'),
+    (KEY_EQUAL, '=', '+'),
+    (KEY_Q, 'q', 'Q'), (KEY_W, 'w', 'W'), (KEY_E, 'e', 'E'), (KEY_R, 'r', 'R'),
+    (KEY_T, 't', 'T'), (KEY_Y, 'y', 'Y'), (KEY_U, 'u', 'U'), (KEY_I, 'i', 'I'),
+    (KEY_O, 'o', 'O'), (KEY_P, 'p', 'P'), (KEY_LEFTBRACE, '[', '{'), (KEY_RIGHTBRACE, ']', '}'),
+    (KEY_BACKSLASH, '\\', '|'),
+    (KEY_A, 'a', 'A'), (KEY_S, 's', 'S'), (KEY_D, 'd', 'D'), (KEY_F, 'f', 'F'),
+    (KEY_G, 'g', 'G'), (KEY_H, 'h', 'H'), (KEY_J, 'j', 'J'), (KEY_K, 'k', 'K'),
+    (KEY_L, 'l', 'L'), (KEY_SEMICOLON, ';', ':'), (KEY_APOSTROPHE, '\'', '"'),
+    (KEY_Z, 'z', 'Z'), (KEY_X, 'x', 'X'), (KEY_C, 'c', 'C'), (KEY_V, 'v', 'V'),
+    (KEY_B, 'b', 'B'), (KEY_N, 'n', 'N'), (KEY_M, 'm', 'M'), (KEY_COMMA, ',', '<'),
+    (KEY_DOT, '.', '>'), (KEY_SLASH, '/', '?'),
+];
+
+const FRENCH_KEYS: &[(i32, char, char)] = &[
+    (KEY_GRAVE, '²', '\0'), (KEY_1, '&', '1'), (KEY_2, 'é', '2'), (KEY_3, '"', '3'),
+    (KEY_4, '\'', '4'), (KEY_5, '(', '5'), (KEY_6, '-', '6'), (KEY_7, 'è', '7'),
+    (KEY_8, '_', '8'), (KEY_9, 'ç', '9'), (KEY_0, 'à', '0'), (KEY_MINUS, ')', '°'),
+    (KEY_EQUAL, '=', '+'),
+    (KEY_Q, 'a', 'A'), (KEY_W, 'z', 'Z'), (KEY_E, 'e', 'E'), (KEY_R, 'r', 'R'),
+    (KEY_T, 't', 'T'), (KEY_Y, 'y', 'Y'), (KEY_U, 'u', 'U'), (KEY_I, 'i', 'I'),
+    (KEY_O, 'o', 'O'), (KEY_P, 'p', 'P'), (KEY_RIGHTBRACE, '$', '£'),
+    (KEY_A, 'q', 'Q'), (KEY_S, 's', 'S'), (KEY_D, 'd', 'D'), (KEY_F, 'f', 'F'),
+    (KEY_G, 'g', 'G'), (KEY_H, 'h', 'H'), (KEY_J, 'j', 'J'), (KEY_K, 'k', 'K'),
+    (KEY_L, 'l', 'L'), (KEY_SEMICOLON, 'm', 'M'), (KEY_APOSTROPHE, 'ù', '%'),
+    (KEY_BACKSLASH, '*', 'µ'),
+    (KEY_102ND, '<', '>'), (KEY_Z, 'w', 'W'), (KEY_X, 'x', 'X'), (KEY_C, 'c', 'C'),
+    (KEY_V, 'v', 'V'), (KEY_B, 'b', 'B'), (KEY_N, 'n', 'N'), (KEY_M, ',', '?'),
+    (KEY_COMMA, ';', '.'), (KEY_DOT, ':', '/'), (KEY_SLASH, '!', '§'),
+];
+
+const GERMAN_KEYS: &[(i32, char, char)] = &[
+    (KEY_GRAVE, '\0', '°'), (KEY_1, '1', '!'), (KEY_2, '2', '"'), (KEY_3, '3', '§'),
+    (KEY_4, '4', '$'), (KEY_5, '5', '%'), (KEY_6, '6', '&'), (KEY_7, '7', '/'),
+    (KEY_8, '8', '('), (KEY_9, '9', ')'), (KEY_0, '0', '='), (KEY_MINUS, 'ß', '?'),
+    (KEY_Q, 'q', 'Q'), (KEY_W, 'w', 'W'), (KEY_E, 'e', 'E'), (KEY_R, 'r', 'R'),
+    (KEY_T, 't', 'T'), (KEY_Y, 'z', 'Z'), (KEY_U, 'u', 'U'), (KEY_I, 'i', 'I'),
+    (KEY_O, 'o', 'O'), (KEY_P, 'p', 'P'), (KEY_LEFTBRACE, 'ü', 'Ü'), (KEY_RIGHTBRACE, '+', '*'),
+    (KEY_A, 'a', 'A'), (KEY_S, 's', 'S'), (KEY_D, 'd', 'D'), (KEY_F, 'f', 'F'),
+    (KEY_G, 'g', 'G'), (KEY_H, 'h', 'H'), (KEY_J, 'j', 'J'), (KEY_K, 'k', 'K'),
+    (KEY_L, 'l', 'L'), (KEY_SEMICOLON, 'ö', 'Ö'), (KEY_APOSTROPHE, 'ä', 'Ä'),
+    (KEY_BACKSLASH, '#', '\''),
+    (KEY_102ND, '<', '>'), (KEY_Z, 'y', 'Y'), (KEY_X, 'x', 'X'), (KEY_C, 'c', 'C'),
+    (KEY_V, 'v', 'V'), (KEY_B, 'b', 'B'), (KEY_N, 'n', 'N'), (KEY_M, 'm', 'M'),
+    (KEY_COMMA, ',', ';'), (KEY_DOT, '.', ':'), (KEY_SLASH, '-', '_'),
+];
+
+/// Global keyboard layout setting
+static LAYOUT: Lazy<Mutex<Layout>> = Lazy::new(|| Mutex::new(Layout::Us));
+
+pub fn set_layout(layout: Layout) {
+    log::info!("[INPUT] Keyboard layout set to: {:?}", layout);
+    *LAYOUT.lock().unwrap() = layout;
+}
+
+/// Keycode and whether shift must be held to produce `c`
+pub fn char_to_key(c: char) -> Option<(i32, bool)> {
+    let layout = *LAYOUT.lock().unwrap();
+    layout.char_to_key(c)
+}
+
+/// Keycode of a physical key, named as in the web `KeyboardEvent.code`
+pub fn code_to_key(code: &str) -> Option<i32> {
+    const LETTERS: [i32; 26] = [
+        KEY_A, KEY_B, KEY_C, KEY_D, KEY_E, KEY_F, KEY_G, KEY_H, KEY_I, KEY_J, KEY_K, KEY_L, KEY_M,
+        KEY_N, KEY_O, KEY_P, KEY_Q, KEY_R, KEY_S, KEY_T, KEY_U, KEY_V, KEY_W, KEY_X, KEY_Y, KEY_Z,
+    ];
+    const DIGITS: [i32; 10] = [KEY_0, KEY_1, KEY_2, KEY_3, KEY_4, KEY_5, KEY_6, KEY_7, KEY_8, KEY_9];
+    const FUNCTION_KEYS: [i32; 12] = [
+        KEY_F1, KEY_F2, KEY_F3, KEY_F4, KEY_F5, KEY_F6, KEY_F7, KEY_F8, KEY_F9, KEY_F10, KEY_F11, KEY_F12,
+    ];
+
+    if let Some(letter) = code.strip_prefix("Key") {
+        let letter = letter.chars().next().filter(|c| c.is_ascii_uppercase() && letter.len() == 1)?;
+        return Some(LETTERS[letter as usize - 'A' as usize]);
+    }
+    if let Some(digit) = code.strip_prefix("Digit") {
+        let digit: usize = digit.parse().ok().filter(|d| *d < 10)?;
+        return Some(DIGITS[digit]);
+    }
+    if let Some(number) = code.strip_prefix('F') {
+        if let Ok(number) = number.parse::<usize>() {
+            return FUNCTION_KEYS.get(number.checked_sub(1)?).copied();
+        }
+    }
+
+    let key = match code {
+        "Escape" => KEY_ESC,
+        "Minus" => KEY_MINUS,
+        "Equal" => KEY_EQUAL,
+        "Backspace" => KEY_BACKSPACE,
+        "Tab" => KEY_TAB,
+        "BracketLeft" => KEY_LEFTBRACE,
+        "BracketRight" => KEY_RIGHTBRACE,
+        "Enter" => KEY_ENTER,
+        "Semicolon" => KEY_SEMICOLON,
+        "Quote" => KEY_APOSTROPHE,
+        "Backquote" => KEY_GRAVE,
+        "Backslash" => KEY_BACKSLASH,
+        "IntlBackslash" => KEY_102ND,
+        "Comma" => KEY_COMMA,
+        "Period" => KEY_DOT,
+        "Slash" => KEY_SLASH,
+        "Space" => KEY_SPACE,
+        "CapsLock" => KEY_CAPSLOCK,
+        "ShiftLeft" => KEY_LEFTSHIFT,
+        "ShiftRight" => KEY_RIGHTSHIFT,
+        "ControlLeft" => KEY_LEFTCTRL,
+        "ControlRight" => KEY_RIGHTCTRL,
+        "AltLeft" => KEY_LEFTALT,
+        "AltRight" => KEY_RIGHTALT,
+        "MetaLeft" => KEY_LEFTMETA,
+        "MetaRight" => KEY_RIGHTMETA,
+        "ContextMenu" => KEY_COMPOSE,
+        "ArrowUp" => KEY_UP,
+        "ArrowDown" => KEY_DOWN,
+        "ArrowLeft" => KEY_LEFT,
+        "ArrowRight" => KEY_RIGHT,
+        "Home" => KEY_HOME,
+        "End" => KEY_END,
+        "PageUp" => KEY_PAGEUP,
+        "PageDown" => KEY_PAGEDOWN,
+        "Insert" => KEY_INSERT,
+        "Delete" => KEY_DELETE,
+        "PrintScreen" => KEY_SYSRQ,
+        "ScrollLock" => KEY_SCROLLLOCK,
+        "Pause" => KEY_PAUSE,
+        "NumLock" => KEY_NUMLOCK,
+        _ => return None,
+    };
+    Some(key)
+}
+
+/// Keycode of an X11 keysym and whether shift must be held to produce it
+pub fn keysym_to_key(keysym: u32) -> Option<(i32, bool)> {
+    let key = match keysym {
+        0xff08 => KEY_BACKSPACE,
+        0xff09 => KEY_TAB,
+        0xff0d => KEY_ENTER,
+        0xff1b => KEY_ESC,
+        0xffff => KEY_DELETE,
+        0xff50 => KEY_HOME,
+        0xff51 => KEY_LEFT,
+        0xff52 => KEY_UP,
+        0xff53 => KEY_RIGHT,
+        0xff54 => KEY_DOWN,
+        0xff55 => KEY_PAGEUP,
+        0xff56 => KEY_PAGEDOWN,
+        0xff57 => KEY_END,
+        0xff63 => KEY_INSERT,
+        0xff67 => KEY_COMPOSE,
+        0xffbe..=0xffc9 => return code_to_key(&format!("F{}", keysym - 0xffbe + 1)).map(|key| (key, false)),
+        0xffe1 => KEY_LEFTSHIFT,
+        0xffe2 => KEY_RIGHTSHIFT,
+        0xffe3 => KEY_LEFTCTRL,
+        0xffe4 => KEY_RIGHTCTRL,
+        0xffe5 => KEY_CAPSLOCK,
+        0xffe9 => KEY_LEFTALT,
+        0xffea => KEY_RIGHTALT,
+        0xffeb => KEY_LEFTMETA,
+        0xffec => KEY_RIGHTMETA,
+        // Latin-1 keysyms equal their code point, the rest are offset
+        0x20..=0xff => return char_to_key(char::from_u32(keysym)?),
+        0x0100_0100..=0x0110_ffff => return char_to_key(char::from_u32(keysym - 0x0100_0000)?),
+        _ => return None,
+    };
+    Some((key, false))
+}
+
+/// Linux keycode that the ROM's `Generic.kl` maps to an Android `KEYCODE_*`
+pub fn android_keycode_to_key(keycode: i32) -> Option<i32> {
+    const LETTERS: [i32; 26] = [
+        KEY_A, KEY_B, KEY_C, KEY_D, KEY_E, KEY_F, KEY_G, KEY_H, KEY_I, KEY_J, KEY_K, KEY_L, KEY_M,
+        KEY_N, KEY_O, KEY_P, KEY_Q, KEY_R, KEY_S, KEY_T, KEY_U, KEY_V, KEY_W, KEY_X, KEY_Y, KEY_Z,
+    ];
+    const DIGITS: [i32; 10] = [KEY_0, KEY_1, KEY_2, KEY_3, KEY_4, KEY_5, KEY_6, KEY_7, KEY_8, KEY_9];
+    const FUNCTION_KEYS: [i32; 12] = [
+        KEY_F1, KEY_F2, KEY_F3, KEY_F4, KEY_F5, KEY_F6, KEY_F7, KEY_F8, KEY_F9, KEY_F10, KEY_F11, KEY_F12,
+    ];
+
+    let key = match keycode {
+        3 => KEY_HOMEPAGE,
+        4 => KEY_BACK,
+        7..=16 => DIGITS[(keycode - 7) as usize],
+        19 => KEY_UP,
+        20 => KEY_DOWN,
+        21 => KEY_LEFT,
+        22 => KEY_RIGHT,
+        24 => KEY_VOLUMEUP,
+        25 => KEY_VOLUMEDOWN,
+        26 => KEY_POWER,
+        27 => KEY_CAMERA,
+        29..=54 => LETTERS[(keycode - 29) as usize],
+        55 => KEY_COMMA,
+        56 => KEY_DOT,
+        57 => KEY_LEFTALT,
+        58 => KEY_RIGHTALT,
+        59 => KEY_LEFTSHIFT,
+        60 => KEY_RIGHTSHIFT,
+        61 => KEY_TAB,
+        62 => KEY_SPACE,
+        66 => KEY_ENTER,
+        67 => KEY_BACKSPACE,
+        68 => KEY_GRAVE,
+        69 => KEY_MINUS,
+        70 => KEY_EQUAL,
+        71 => KEY_LEFTBRACE,
+        72 => KEY_RIGHTBRACE,
+        73 => KEY_BACKSLASH,
+        74 => KEY_SEMICOLON,
+        75 => KEY_APOSTROPHE,
+        76 => KEY_SLASH,
+        82 => KEY_MENU,
+        84 => KEY_SEARCH,
+        85 => KEY_PLAYPAUSE,
+        86 => KEY_STOPCD,
+        87 => KEY_NEXTSONG,
+        88 => KEY_PREVIOUSSONG,
+        92 => KEY_PAGEUP,
+        93 => KEY_PAGEDOWN,
+        111 => KEY_ESC,
+        112 => KEY_DELETE,
+        113 => KEY_LEFTCTRL,
+        114 => KEY_RIGHTCTRL,
+        115 => KEY_CAPSLOCK,
+        116 => KEY_SCROLLLOCK,
+        117 => KEY_LEFTMETA,
+        118 => KEY_RIGHTMETA,
+        120 => KEY_SYSRQ,
+        121 => KEY_PAUSE,
+        122 => KEY_HOME,
+        123 => KEY_END,
+        124 => KEY_INSERT,
+        131..=142 => FUNCTION_KEYS[(keycode - 131) as usize],
+        143 => KEY_NUMLOCK,
+        164 => KEY_MUTE,
+        187 => KEY_APPSELECT,
+        223 => KEY_SLEEP,
+        224 => KEY_WAKEUP,
+        _ => return None,
+    };
+    Some(key)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_layout_from_name() {
+        assert_eq!(Layout::from_name("US"), Some(Layout::Us));
+        assert_eq!(Layout::from_name("azerty"), Some(Layout::French));
+        assert_eq!(Layout::from_name("de"), Some(Layout::German));
+        assert_eq!(Layout::from_name("dvorak"), None);
+    }
+
+    #[test]
+    fn test_char_to_key_per_layout() {
+        assert_eq!(Layout::Us.char_to_key('a'), Some((KEY_A, false)));
+        assert_eq!(Layout::Us.char_to_key('A'), Some((KEY_A, true)));
+        assert_eq!(Layout::Us.char_to_key('@'), Some((KEY_2, true)));
+        assert_eq!(Layout::French.char_to_key('a'), Some((KEY_Q, false)));
+        assert_eq!(Layout::French.char_to_key('1'), Some((KEY_1, true)));
+        assert_eq!(Layout::German.char_to_key('z'), Some((KEY_Y, false)));
+        assert_eq!(Layout::German.char_to_key('ß'), Some((KEY_MINUS, false)));
+        assert_eq!(Layout::Us.char_to_key(' '), Some((KEY_SPACE, false)));
+        assert_eq!(Layout::Us.char_to_key('\n'), Some((KEY_ENTER, false)));
+        assert_eq!(Layout::Us.char_to_key('é'), None);
+        assert_eq!(Layout::Us.char_to_key('\0'), None);
+    }
+
+    #[test]
+    fn test_tables_are_unambiguous() {
+        for layout in [Layout::Us, Layout::French, Layout::German] {
+            let mut keys = Vec::new();
+            let mut chars = Vec::new();
+            for &(key, base, shifted) in layout.table() {
+                assert!(!keys.contains(&key), "{:?} lists key {} twice", layout, key);
+                keys.push(key);
+                for c in [base, shifted] {
+                    if c != '\0' {
+                        assert!(!chars.contains(&c), "{:?} types {:?} twice", layout, c);
+                        chars.push(c);
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_code_to_key() {
+        assert_eq!(code_to_key("KeyA"), Some(KEY_A));
+        assert_eq!(code_to_key("KeyZ"), Some(KEY_Z));
+        assert_eq!(code_to_key("Digit0"), Some(KEY_0));
+        assert_eq!(code_to_key("F12"), Some(KEY_F12));
+        assert_eq!(code_to_key("NumLock"), Some(KEY_NUMLOCK));
+        assert_eq!(code_to_key("Keya"), None);
+        assert_eq!(code_to_key("KeyAB"), None);
+        assert_eq!(code_to_key("Digit10"), None);
+        assert_eq!(code_to_key("F13"), None);
+        assert_eq!(code_to_key(""), None);
+    }
+
+    #[test]
+    fn test_keysym_to_key() {
+        assert_eq!(keysym_to_key(0xff0d), Some((KEY_ENTER, false)));
+        assert_eq!(keysym_to_key(0xffbe), Some((KEY_F1, false)));
+        assert_eq!(keysym_to_key(0xffc9), Some((KEY_F12, false)));
+        assert_eq!(keysym_to_key(0x1234_5678), None);
+    }
+
+    #[test]
+    fn test_android_keycode_to_key() {
+        assert_eq!(android_keycode_to_key(4), Some(KEY_BACK));
+        assert_eq!(android_keycode_to_key(7), Some(KEY_0));
+        assert_eq!(android_keycode_to_key(16), Some(KEY_9));
+        assert_eq!(android_keycode_to_key(29), Some(KEY_A));
+        assert_eq!(android_keycode_to_key(54), Some(KEY_Z));
+        assert_eq!(android_keycode_to_key(131), Some(KEY_F1));
+        assert_eq!(android_keycode_to_key(142), Some(KEY_F12));
+        assert_eq!(android_keycode_to_key(0), None);
+    }
+}
@@ -3,7 +3,8 @@
 // file, You can obtain one at https://mozilla.org/MPL/2.0/.
 
 use jni::objects::JValue;
-use jni::sys::{jclass, jfloat, jint, jobject, JNI_ERR, jstring};
+use jni::signature::{JavaType, Primitive};
+use jni::sys::{jboolean, jclass, jfloat, jint, jintArray, jobject, JNI_ERR, JNI_FALSE, jstring};
 use jni::JNIEnv;
 use jni::{JavaVM, NativeMethod};
 use log::{debug, error, info, Level};
@@ -12,9 +13,19 @@ use std::ffi::c_void;
 
 use android_logger::Config;
 
+mod console_log;
+mod container;
+mod crash_dumps;
+mod events;
 mod input;
+mod input_latency;
+mod input_record;
+mod jni_cache;
+mod keymap;
 mod renderer_bindings;
 mod renderer_new;
+mod rootfs_check;
+mod seccomp;
 mod core;
 
 // Reference the interp symbol from C to force it to be linked
@@ -89,6 +100,19 @@ pub fn renderer_init(
     );
 }
 
+#[no_mangle]
+pub fn set_event_listener(env: JNIEnv, _clz: jclass, listener: jobject) {
+    debug!("set_event_listener");
+    if listener.is_null() {
+        events::set_listener(None);
+        return;
+    }
+    match env.new_global_ref(listener) {
+        Ok(listener) => events::set_listener(Some(listener)),
+        Err(e) => error!("Failed to keep event listener: {}", e),
+    }
+}
+
 #[no_mangle]
 pub fn set_renderer_type(
     _env: JNIEnv,
@@ -99,6 +123,17 @@ pub fn set_renderer_type(
     core::set_renderer_type(use_new_renderer != 0);
 }
 
+#[no_mangle]
+pub fn set_renderer_backend(
+    env: JNIEnv,
+    _clz: jclass,
+    backend: jstring,
+) -> jboolean {
+    let backend: String = env.get_string(backend.into()).unwrap().into();
+    debug!("set_renderer_backend: {}", backend);
+    core::set_renderer_backend(&backend) as jboolean
+}
+
 #[no_mangle]
 pub fn set_debug_renderer(
     _env: JNIEnv,
@@ -120,6 +155,54 @@ pub fn set_debug_log_dir(
     core::set_debug_log_dir(log_dir_path);
 }
 
+#[no_mangle]
+pub fn add_shared_folder(env: JNIEnv, _clz: jclass, host_dir: jstring, container_dir: jstring) -> jboolean {
+    let host_dir: String = env.get_string(host_dir.into()).unwrap().into();
+    let container_dir: String = env.get_string(container_dir.into()).unwrap().into();
+    debug!("add_shared_folder: {} -> {}", host_dir, container_dir);
+    core::add_shared_folder(host_dir, container_dir) as jboolean
+}
+
+#[no_mangle]
+pub fn set_log_rotation(_env: JNIEnv, _clz: jclass, max_kb: jint, max_minutes: jint, keep: jint, compress: jint) {
+    debug!("set_log_rotation: {} KiB, {} min, keep {}, compress {}", max_kb, max_minutes, keep, compress);
+    core::set_log_rotation(max_kb, max_minutes, keep, compress != 0);
+}
+
+#[no_mangle]
+pub fn set_container_hostname(
+    env: JNIEnv,
+    _clz: jclass,
+    hostname: jstring,
+) {
+    let hostname: String = env.get_string(hostname.into()).unwrap().into();
+    debug!("set_container_hostname: {}", hostname);
+    core::set_container_hostname(hostname);
+}
+
+#[no_mangle]
+pub fn set_host_time_settings(
+    env: JNIEnv,
+    _clz: jclass,
+    timezone: jstring,
+    locale: jstring,
+) {
+    let timezone: String = env.get_string(timezone.into()).unwrap().into();
+    let locale: String = env.get_string(locale.into()).unwrap().into();
+    debug!("set_host_time_settings: {} {}", timezone, locale);
+    core::set_host_time_settings(timezone, locale);
+}
+
+#[no_mangle]
+pub fn set_seccomp_profile(
+    _env: JNIEnv,
+    _clz: jclass,
+    profile: jint,
+) {
+    debug!("set_seccomp_profile: {}", profile);
+    core::set_seccomp_profile(profile);
+}
+
 #[no_mangle]
 pub fn renderer_reset_window(
     env: JNIEnv,
@@ -149,10 +232,19 @@ pub fn renderer_remove_window(env: JNIEnv, _clz: jclass, surface: jobject) {
     }
 }
 
+#[no_mangle]
+pub fn renderer_destroy(_env: JNIEnv, _clz: jclass) {
+    debug!("renderer_destroy");
+    core::destroy_renderer();
+}
+
 #[no_mangle]
 pub fn handle_touch(env: JNIEnv, _clz: jclass, event: jobject) {
-    // TODO: cache the field id.
-    let ptr = env.get_field(event, "mNativePtr", "J").unwrap();
+    let ptr = match jni_cache::motion_event_native_ptr() {
+        Some(field) => env.get_field_unchecked(event, field, JavaType::Primitive(Primitive::Long)),
+        None => env.get_field(event, "mNativePtr", "J"),
+    }
+    .unwrap();
 
     if let JValue::Long(p) = ptr {
         let ev = unsafe {
@@ -166,9 +258,246 @@ pub fn handle_touch(env: JNIEnv, _clz: jclass, event: jobject) {
 }
 
 #[no_mangle]
-pub fn send_key_code(_env: JNIEnv, _clz: jclass, keycode: jint) {
-    debug!("send key code!");
-    input::send_key_code(keycode);
+pub fn freeze_container(_env: JNIEnv, _clz: jclass) {
+    debug!("freeze_container");
+    core::freeze_container();
+}
+
+#[no_mangle]
+pub fn thaw_container(_env: JNIEnv, _clz: jclass) {
+    debug!("thaw_container");
+    core::thaw_container();
+}
+
+#[no_mangle]
+pub fn stop_container(_env: JNIEnv, _clz: jclass) -> jboolean {
+    debug!("stop_container");
+    core::stop_container() as jboolean
+}
+
+#[no_mangle]
+pub fn restart_container(_env: JNIEnv, _clz: jclass) -> jboolean {
+    debug!("restart_container");
+    core::restart_container() as jboolean
+}
+
+#[no_mangle]
+pub fn set_boot_completed(_env: JNIEnv, _clz: jclass, completed: jint) {
+    core::set_boot_completed(completed != 0);
+}
+
+#[no_mangle]
+pub fn get_container_status(env: JNIEnv, _clz: jclass) -> jstring {
+    let status = core::container_status();
+    debug!("get_container_status: {}", status);
+    match env.new_string(status) {
+        Ok(s) => s.into_inner(),
+        Err(e) => {
+            error!("get_container_status: failed to create string: {:?}", e);
+            std::ptr::null_mut()
+        }
+    }
+}
+
+#[no_mangle]
+pub fn check_health(env: JNIEnv, _clz: jclass) -> jstring {
+    let report = core::health_report();
+    debug!("check_health: {}", report);
+    match env.new_string(report) {
+        Ok(s) => s.into_inner(),
+        Err(e) => {
+            error!("check_health: failed to create string: {:?}", e);
+            std::ptr::null_mut()
+        }
+    }
+}
+
+#[no_mangle]
+pub fn set_touch_slots(_env: JNIEnv, _clz: jclass, slots: jint) {
+    debug!("set_touch_slots: {}", slots);
+    input::set_touch_slots(slots);
+}
+
+#[no_mangle]
+pub fn set_touch_transform(
+    _env: JNIEnv,
+    _clz: jclass,
+    offset_x: jfloat,
+    offset_y: jfloat,
+    scale_x: jfloat,
+    scale_y: jfloat,
+    flags: jint,
+) {
+    input::set_touch_transform(offset_x, offset_y, scale_x, scale_y, flags);
+}
+
+#[no_mangle]
+pub fn send_key_event(_env: JNIEnv, _clz: jclass, keycode: jint, action: jint, meta_state: jint, repeat: jint) {
+    debug!("send_key_event: keycode={} action={} meta={:#x} repeat={}", keycode, action, meta_state, repeat);
+    input::send_key_event(keycode, action, meta_state, repeat);
+}
+
+#[no_mangle]
+pub fn send_key(_env: JNIEnv, _clz: jclass, key: jint, pressed: jint) {
+    input::send_key(key, pressed != 0);
+}
+
+#[no_mangle]
+pub fn send_key_with_meta(_env: JNIEnv, _clz: jclass, key: jint, pressed: jint, meta_state: jint) {
+    input::send_key_with_meta(key, pressed != 0, meta_state);
+}
+
+#[no_mangle]
+pub fn release_all_keys(_env: JNIEnv, _clz: jclass) {
+    input::release_all_keys();
+}
+
+#[no_mangle]
+pub fn send_key_by_code(env: JNIEnv, _clz: jclass, code: jstring, pressed: jint) {
+    let code: String = env.get_string(code.into()).unwrap().into();
+    input::send_code(&code, pressed != 0);
+}
+
+#[no_mangle]
+pub fn send_keysym(_env: JNIEnv, _clz: jclass, keysym: jint, pressed: jint) {
+    input::send_keysym(keysym as u32, pressed != 0);
+}
+
+#[no_mangle]
+pub fn set_keyboard_layout(env: JNIEnv, _clz: jclass, layout: jstring) {
+    let layout: String = env.get_string(layout.into()).unwrap().into();
+    debug!("set_keyboard_layout: {}", layout);
+    input::set_keyboard_layout(&layout);
+}
+
+#[no_mangle]
+pub fn type_text(env: JNIEnv, _clz: jclass, text: jstring) {
+    let text: String = env.get_string(text.into()).unwrap().into();
+    input::type_text(&text);
+}
+
+#[no_mangle]
+pub fn commit_text(env: JNIEnv, _clz: jclass, text: jstring) -> jint {
+    let text: String = env.get_string(text.into()).unwrap().into();
+    input::type_text(&text) as jint
+}
+
+#[no_mangle]
+pub fn set_physical_keyboard(_env: JNIEnv, _clz: jclass, attached: jint) {
+    debug!("set_physical_keyboard: {}", attached);
+    if attached != 0 {
+        input::attach_keyboard();
+    } else {
+        input::detach_keyboard();
+    }
+}
+
+#[no_mangle]
+pub fn delete_surrounding_text(_env: JNIEnv, _clz: jclass, before: jint, after: jint) {
+    input::delete_surrounding_text(before, after);
+}
+
+#[no_mangle]
+pub fn send_gamepad_button(_env: JNIEnv, _clz: jclass, button: jint, pressed: jint) {
+    input::send_gamepad_button(button, pressed != 0);
+}
+
+#[no_mangle]
+pub fn send_gamepad_axis(_env: JNIEnv, _clz: jclass, axis: jint, value: jint) {
+    input::send_gamepad_axis(axis, value);
+}
+
+#[no_mangle]
+pub fn send_gamepad_axes(env: JNIEnv, _clz: jclass, axes: jintArray) {
+    let len = env.get_array_length(axes).unwrap_or(0) as usize;
+    let mut values = vec![0; len];
+    if env.get_int_array_region(axes, 0, &mut values).is_err() {
+        return;
+    }
+    let pairs: Vec<(i32, i32)> = values.chunks_exact(2).map(|p| (p[0], p[1])).collect();
+    input::send_gamepad_axes(&pairs);
+}
+
+#[no_mangle]
+pub fn send_mouse_event(_env: JNIEnv, _clz: jclass, dx: jint, dy: jint, wheel: jint, hwheel: jint, buttons: jint) {
+    input::send_mouse_event(dx, dy, wheel, hwheel, buttons);
+}
+
+// The arguments mirror Renderer.sendStylusEvent
+#[allow(clippy::too_many_arguments)]
+#[no_mangle]
+pub fn send_stylus_event(
+    _env: JNIEnv,
+    _clz: jclass,
+    x: jint,
+    y: jint,
+    pressure: jfloat,
+    tilt_x: jint,
+    tilt_y: jint,
+    state: jint,
+    button: jint,
+) {
+    let pressure = (pressure * input::STYLUS_PRESSURE_MAX as f32) as i32;
+    input::send_stylus_event(x, y, pressure, tilt_x, tilt_y, state, button != 0);
+}
+
+#[no_mangle]
+pub fn start_input_recording(_env: JNIEnv, _clz: jclass) {
+    debug!("start_input_recording");
+    input_record::start();
+}
+
+#[no_mangle]
+pub fn stop_input_recording(_env: JNIEnv, _clz: jclass) -> jint {
+    debug!("stop_input_recording");
+    input_record::stop().map_or(-1, |id| id as jint)
+}
+
+#[no_mangle]
+pub fn replay_input(_env: JNIEnv, _clz: jclass, id: jint, speed: jfloat) -> jboolean {
+    debug!("replay_input: {} at {}x", id, speed);
+    if id < 0 {
+        return JNI_FALSE;
+    }
+    input_record::replay(id as usize, speed) as jboolean
+}
+
+#[no_mangle]
+pub fn delete_input_recording(_env: JNIEnv, _clz: jclass, id: jint) -> jboolean {
+    debug!("delete_input_recording: {}", id);
+    if id < 0 {
+        return JNI_FALSE;
+    }
+    input_record::delete(id as usize) as jboolean
+}
+
+#[no_mangle]
+pub fn clear_input_recordings(_env: JNIEnv, _clz: jclass) {
+    debug!("clear_input_recordings");
+    input_record::clear();
+}
+
+#[no_mangle]
+pub fn send_scroll(_env: JNIEnv, _clz: jclass, dx: jint, dy: jint) {
+    input::send_scroll(dx, dy);
+}
+
+#[no_mangle]
+pub fn set_input_latency_mode(_env: JNIEnv, _clz: jclass, enabled: jint) {
+    debug!("set_input_latency_mode: {}", enabled);
+    input_latency::set_enabled(enabled != 0);
+}
+
+#[no_mangle]
+pub fn get_input_latency(env: JNIEnv, _clz: jclass) -> jstring {
+    let report = input_latency::report();
+    match env.new_string(report) {
+        Ok(s) => s.into_inner(),
+        Err(e) => {
+            error!("get_input_latency: failed to create string: {:?}", e);
+            std::ptr::null_mut()
+        }
+    }
 }
 
 unsafe fn register_natives(jvm: &JavaVM, class_name: &str, methods: &[NativeMethod]) -> jint {
@@ -246,14 +575,61 @@ unsafe fn JNI_OnLoad(jvm: JavaVM, _reserved: *mut c_void) -> jint {
             renderer_remove_window,
             "(Landroid/view/Surface;)V"
         ),
+        jni_method!(destroy, renderer_destroy, "()V"),
         jni_method!(handleTouch, handle_touch, "(Landroid/view/MotionEvent;)V"),
-        jni_method!(sendKeycode, send_key_code, "(I)V"),
+        jni_method!(setTouchSlots, set_touch_slots, "(I)V"),
+        jni_method!(setTouchTransform, set_touch_transform, "(FFFFI)V"),
+        jni_method!(sendKeyEvent, send_key_event, "(IIII)V"),
+        jni_method!(sendKey, send_key, "(II)V"),
+        jni_method!(sendKeyWithMeta, send_key_with_meta, "(III)V"),
+        jni_method!(releaseAllKeys, release_all_keys, "()V"),
+        jni_method!(setPhysicalKeyboard, set_physical_keyboard, "(I)V"),
+        jni_method!(sendKeyByCode, send_key_by_code, "(Ljava/lang/String;I)V"),
+        jni_method!(sendKeysym, send_keysym, "(II)V"),
+        jni_method!(setKeyboardLayout, set_keyboard_layout, "(Ljava/lang/String;)V"),
+        jni_method!(typeText, type_text, "(Ljava/lang/String;)V"),
+        jni_method!(commitText, commit_text, "(Ljava/lang/String;)I"),
+        jni_method!(deleteSurroundingText, delete_surrounding_text, "(II)V"),
+        jni_method!(sendGamepadButton, send_gamepad_button, "(II)V"),
+        jni_method!(sendGamepadAxis, send_gamepad_axis, "(II)V"),
+        jni_method!(sendGamepadAxes, send_gamepad_axes, "([I)V"),
+        jni_method!(sendMouseEvent, send_mouse_event, "(IIIII)V"),
+        jni_method!(sendScroll, send_scroll, "(II)V"),
+        jni_method!(sendStylusEvent, send_stylus_event, "(IIFIIII)V"),
+        jni_method!(startInputRecording, start_input_recording, "()V"),
+        jni_method!(stopInputRecording, stop_input_recording, "()I"),
+        jni_method!(replayInput, replay_input, "(IF)Z"),
+        jni_method!(deleteInputRecording, delete_input_recording, "(I)Z"),
+        jni_method!(clearInputRecordings, clear_input_recordings, "()V"),
+        jni_method!(setInputLatencyMode, set_input_latency_mode, "(I)V"),
+        jni_method!(getInputLatency, get_input_latency, "()Ljava/lang/String;"),
+        jni_method!(setEventListener, set_event_listener, "(Lio/twoyi/Renderer$EventListener;)V"),
         jni_method!(setRendererType, set_renderer_type, "(I)V"),
+        jni_method!(setRendererBackend, set_renderer_backend, "(Ljava/lang/String;)Z"),
         jni_method!(setDebugRenderer, set_debug_renderer, "(I)V"),
         jni_method!(setDebugLogDir, set_debug_log_dir, "(Ljava/lang/String;)V"),
+        jni_method!(setContainerHostname, set_container_hostname, "(Ljava/lang/String;)V"),
+        jni_method!(addSharedFolder, add_shared_folder, "(Ljava/lang/String;Ljava/lang/String;)Z"),
+        jni_method!(setLogRotation, set_log_rotation, "(IIII)V"),
+        jni_method!(setSeccompProfile, set_seccomp_profile, "(I)V"),
+        jni_method!(setHostTimeSettings, set_host_time_settings, "(Ljava/lang/String;Ljava/lang/String;)V"),
+        jni_method!(freezeContainer, freeze_container, "()V"),
+        jni_method!(thawContainer, thaw_container, "()V"),
+        jni_method!(stopContainer, stop_container, "()Z"),
+        jni_method!(restartContainer, restart_container, "()Z"),
+        jni_method!(checkHealth, check_health, "()Ljava/lang/String;"),
+        jni_method!(getContainerStatus, get_container_status, "()Ljava/lang/String;"),
+        jni_method!(setBootCompleted, set_boot_completed, "(I)V"),
     ];
 
     let result = register_natives(&jvm, class_name, jni_methods.as_ref());
+    events::set_java_vm(&jvm);
+    if let Ok(env) = jvm.get_env() {
+        if let Err(e) = jni_cache::init(&env) {
+            error!("JNI cache not initialized, falling back to lookups by name: {:?}", e);
+            let _ = env.exception_clear();
+        }
+    }
     debug!("JNI_OnLoad completed with result: {}", result);
     result
 }
@@ -275,7 +651,16 @@ pub extern "C" fn twoyi_print_help() {
     let _ = writeln!(io::stdout(), "\nExported Functions:");
     let _ = writeln!(io::stdout(), "  twoyi_start_input_system(width, height) - Start input system");
     let _ = writeln!(io::stdout(), "  twoyi_print_help() - Show this help");
-    let _ = writeln!(io::stdout(), "  twoyi_send_keycode(keycode) - Send a keycode event");
+    let _ = writeln!(io::stdout(), "  twoyi_send_keycode(keycode) - Tap a key given as an Android keycode");
+    let _ = writeln!(io::stdout(), "  twoyi_send_key(key, pressed) - Press or release a key (KEY_*)");
+    let _ = writeln!(io::stdout(), "  twoyi_type_text(text) - Type a UTF-8 string on the virtual keyboard");
+    let _ = writeln!(io::stdout(), "  twoyi_send_keysym(keysym, pressed) - Press or release the key for an X11 keysym");
+    let _ = writeln!(io::stdout(), "  twoyi_set_keyboard_layout(name) - Set the container keyboard layout (us, fr, de)");
+    let _ = writeln!(io::stdout(), "  twoyi_gamepad_button(button, pressed) - Press or release a gamepad button (BTN_*)");
+    let _ = writeln!(io::stdout(), "  twoyi_gamepad_axis(axis, value) - Move a gamepad axis (ABS_*)");
+    let _ = writeln!(io::stdout(), "  twoyi_mouse_event(dx, dy, wheel, hwheel, buttons) - Move, scroll or click the mouse");
+    let _ = writeln!(io::stdout(), "  twoyi_scroll(dx, dy) - Scroll by wheel detents");
+    let _ = writeln!(io::stdout(), "  twoyi_stylus_event(x, y, pressure, tilt_x, tilt_y, state, button) - Report the stylus (state 0 away, 1 hover, 2 contact)");
     let _ = writeln!(io::stdout(), "\nUsage from shell:");
     let _ = writeln!(io::stdout(), "  This library can be loaded via System.loadLibrary(\"twoyi\") in Android apps");
     let _ = writeln!(io::stdout(), "  Or called from shell using the twoyi wrapper script");
@@ -287,9 +672,84 @@ pub extern "C" fn twoyi_send_keycode(keycode: i32) {
     input::send_key_code(keycode);
 }
 
-// Main function for standalone execution when invoked directly or via linker64
+/// Press or release a key - exposed for shell access
+#[no_mangle]
+pub extern "C" fn twoyi_send_key(key: i32, pressed: i32) {
+    input::send_key(key, pressed != 0);
+}
+
+/// Type a NUL-terminated UTF-8 string - exposed for shell access
+///
+/// # Safety
+///
+/// `text` must be null or point to a NUL-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn twoyi_type_text(text: *const libc::c_char) {
+    if text.is_null() {
+        return;
+    }
+    let text = std::ffi::CStr::from_ptr(text);
+    input::type_text(&text.to_string_lossy());
+}
+
+/// Press or release the key for an X11 keysym - exposed for shell access
+#[no_mangle]
+pub extern "C" fn twoyi_send_keysym(keysym: u32, pressed: i32) {
+    input::send_keysym(keysym, pressed != 0);
+}
+
+/// Set the keyboard layout from a NUL-terminated name - exposed for shell access
+///
+/// # Safety
+///
+/// `name` must be null or point to a NUL-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn twoyi_set_keyboard_layout(name: *const libc::c_char) {
+    if name.is_null() {
+        return;
+    }
+    let name = std::ffi::CStr::from_ptr(name);
+    input::set_keyboard_layout(&name.to_string_lossy());
+}
+
+/// Press or release a gamepad button - exposed for shell access
+#[no_mangle]
+pub extern "C" fn twoyi_gamepad_button(button: i32, pressed: i32) {
+    input::send_gamepad_button(button, pressed != 0);
+}
+
+/// Move a gamepad axis - exposed for shell access
 #[no_mangle]
-pub extern "C" fn main(argc: i32, argv: *const *const libc::c_char) -> i32 {
+pub extern "C" fn twoyi_gamepad_axis(axis: i32, value: i32) {
+    input::send_gamepad_axis(axis, value);
+}
+
+/// Move, scroll or click the mouse - exposed for shell access
+#[no_mangle]
+pub extern "C" fn twoyi_mouse_event(dx: i32, dy: i32, wheel: i32, hwheel: i32, buttons: i32) {
+    input::send_mouse_event(dx, dy, wheel, hwheel, buttons);
+}
+
+/// Scroll by wheel detents - exposed for shell access
+#[no_mangle]
+pub extern "C" fn twoyi_scroll(dx: i32, dy: i32) {
+    input::send_scroll(dx, dy);
+}
+
+/// Report the stylus position - exposed for shell access
+#[no_mangle]
+pub extern "C" fn twoyi_stylus_event(x: i32, y: i32, pressure: i32, tilt_x: i32, tilt_y: i32, state: i32, button: i32) {
+    input::send_stylus_event(x, y, pressure, tilt_x, tilt_y, state, button != 0);
+}
+
+/// Main function for standalone execution when invoked directly or via linker64
+///
+/// # Safety
+///
+/// `argv` must be null or hold `argc` pointers to NUL-terminated strings, as
+/// the loader passes them.
+#[no_mangle]
+pub unsafe extern "C" fn main(argc: i32, argv: *const *const libc::c_char) -> i32 {
     use std::io::{self, Write};
     use std::ffi::CStr;
     
@@ -327,6 +787,9 @@ pub extern "C" fn main(argc: i32, argv: *const *const libc::c_char) -> i32 {
     let _ = writeln!(io::stdout(), "  --height <height>     Set virtual display height (default: 1280)");
     let _ = writeln!(io::stdout(), "  --loader <path>       Set loader path");
     let _ = writeln!(io::stdout(), "  --start-input         Start input system only");
+    let _ = writeln!(io::stdout(), "\nCommands:");
+    let _ = writeln!(io::stdout(), "  check [-r <rootfs>] [--loader <path>]");
+    let _ = writeln!(io::stdout(), "                        Validate a rootfs and the host kernel (default: {})", container::ROOTFS_PATH);
     let _ = writeln!(io::stdout(), "\nNote: This library is primarily designed to be loaded by the Twoyi app.");
     let _ = writeln!(io::stdout(), "For full functionality, use it as a JNI library via System.loadLibrary(\"twoyi\")");
     
@@ -334,6 +797,9 @@ pub extern "C" fn main(argc: i32, argv: *const *const libc::c_char) -> i32 {
     let mut width = 720;
     let mut height = 1280;
     let mut start_input = false;
+    let mut check = false;
+    let mut rootfs = container::ROOTFS_PATH.to_string();
+    let mut loader: Option<String> = None;
     
     let mut i = 1;
     while i < args.len() {
@@ -361,11 +827,42 @@ pub extern "C" fn main(argc: i32, argv: *const *const libc::c_char) -> i32 {
             "--start-input" => {
                 start_input = true;
             }
+            "check" => {
+                check = true;
+            }
+            "-r" | "--rootfs" => {
+                i += 1;
+                if i < args.len() {
+                    rootfs = args[i].clone();
+                }
+            }
+            "--loader" => {
+                i += 1;
+                if i < args.len() {
+                    loader = Some(args[i].clone());
+                }
+            }
             _ => {}
         }
         i += 1;
     }
     
+    if check {
+        let _ = writeln!(io::stdout(), "\nChecking rootfs: {}", rootfs);
+        let mut issues = rootfs_check::check_rootfs(&rootfs, loader.as_deref());
+        issues.extend(rootfs_check::check_host());
+        if issues.is_empty() {
+            let _ = writeln!(io::stdout(), "OK: rootfs and kernel look bootable");
+            return 0;
+        }
+        for issue in &issues {
+            let _ = writeln!(io::stdout(), "ERROR: {}", issue.message);
+            let _ = writeln!(io::stdout(), "       hint: {}", issue.hint);
+        }
+        let _ = writeln!(io::stdout(), "{} problem(s) found", issues.len());
+        return 1;
+    }
+
     if start_input {
         let _ = writeln!(io::stdout(), "\nStarting input system with dimensions: {}x{}", width, height);
         twoyi_start_input_system(width, height);
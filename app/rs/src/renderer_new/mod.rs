@@ -37,6 +37,8 @@ pub use renderer::{
     reset_window,
     remove_window,
     set_native_window,
+    destroy_subwindow,
+    status,
 };
 
 /// Set the debug mode for the renderer
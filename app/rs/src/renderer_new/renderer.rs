@@ -16,6 +16,7 @@
 
 use log::{debug, error, info, warn};
 use std::ffi::c_void;
+use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::Mutex;
 use once_cell::sync::Lazy;
 
@@ -36,6 +37,28 @@ struct RendererState {
 // Mark RendererState as Send since we're controlling access via Mutex
 unsafe impl Send for RendererState {}
 
+/// Frames repainted since the renderer last started
+static FRAMES: AtomicU64 = AtomicU64::new(0);
+
+/// Snapshot of a running renderer, for health reporting
+pub struct RendererStatus {
+    pub width: i32,
+    pub height: i32,
+    pub gralloc: bool,
+    pub frames: u64,
+}
+
+/// State of the renderer, `None` if it is not running
+pub fn status() -> Option<RendererStatus> {
+    let renderer = RENDERER.lock().unwrap();
+    renderer.as_ref().map(|state| RendererStatus {
+        width: state.width,
+        height: state.height,
+        gralloc: state.gralloc_manager.is_some(),
+        frames: FRAMES.load(Ordering::Relaxed),
+    })
+}
+
 /// Start the OpenGL renderer
 /// 
 /// This function mimics the old `startOpenGLRenderer` API
@@ -111,6 +134,7 @@ pub fn start_renderer(
     
     let mut renderer = RENDERER.lock().unwrap();
     *renderer = Some(state);
+    FRAMES.store(0, Ordering::Relaxed);
     
     info!("[NEW_RENDERER] ========================================");
     info!("[NEW_RENDERER] New OpenGL renderer started successfully!");
@@ -213,7 +237,6 @@ pub fn remove_window(_window: *mut c_void) -> i32 {
 /// Destroy the OpenGL subwindow
 /// 
 /// This function mimics the old `destroyOpenGLSubwindow` API
-#[allow(dead_code)]
 pub fn destroy_subwindow() -> i32 {
     info!("[NEW_RENDERER] Destroying OpenGL subwindow");
     
@@ -266,6 +289,7 @@ pub fn repaint_display() {
         if let Err(e) = state.gl_context.repaint() {
             error!("[NEW_RENDERER] Failed to repaint display: {}", e);
         } else {
+            FRAMES.fetch_add(1, Ordering::Relaxed);
             debug!("[NEW_RENDERER] Display repainted successfully");
         }
     } else {
@@ -0,0 +1,344 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+//! Rootfs validation
+//!
+//! Checks a rootfs for the problems that otherwise only show up as a
+//! container that silently never boots: missing or foreign-architecture
+//! binaries, missing directories, and wrong permissions. The host kernel is
+//! checked separately, for its version and the binder and ashmem devices the
+//! container's Android needs.
+
+use std::ffi::CString;
+use std::fs::File;
+use std::io::Read;
+use std::os::unix::fs::PermissionsExt;
+use std::path::Path;
+
+/// ELF machine of the host
+#[cfg(target_arch = "aarch64")]
+const HOST_ELF_MACHINE: u16 = 183; // EM_AARCH64
+#[cfg(target_arch = "x86_64")]
+const HOST_ELF_MACHINE: u16 = 62; // EM_X86_64
+
+/// ABI name of the host, as used in `ro.product.cpu.abi`
+#[cfg(target_arch = "aarch64")]
+const HOST_ABI: &str = "arm64-v8a";
+#[cfg(target_arch = "x86_64")]
+const HOST_ABI: &str = "x86_64";
+
+/// Directories the container cannot boot without
+const REQUIRED_DIRS: &[&str] = &["system", "vendor", "data", "dev"];
+
+/// Directories the host side creates sockets in, so they must be writable
+const WRITABLE_DIRS: &[&str] = &["dev/input", "dev/socket", "data"];
+
+/// Oldest kernel the supported Android releases run on
+const MIN_KERNEL: (u32, u32) = (3, 18);
+
+/// Device nodes binder may be exposed as
+const BINDER_DEVICES: &[&str] = &["/dev/binder", "/dev/binderfs/binder"];
+
+/// A single problem found in the rootfs
+pub struct CheckIssue {
+    pub message: String,
+    pub hint: &'static str,
+}
+
+impl CheckIssue {
+    fn new(message: String, hint: &'static str) -> Self {
+        CheckIssue { message, hint }
+    }
+}
+
+/// Verify that `path` is an executable ELF binary built for the host
+fn check_elf(path: &Path, what: &str, issues: &mut Vec<CheckIssue>) {
+    let mut header = [0u8; 20];
+    let read = File::open(path).and_then(|mut f| f.read_exact(&mut header));
+    if let Err(e) = read {
+        issues.push(CheckIssue::new(
+            format!("{} {:?} cannot be read: {}", what, path, e),
+            "re-import the ROM, the file is missing or truncated",
+        ));
+        return;
+    }
+
+    if &header[..4] != b"\x7fELF" {
+        issues.push(CheckIssue::new(
+            format!("{} {:?} is not an ELF binary", what, path),
+            "the ROM is damaged or was extracted without preserving symlinks",
+        ));
+        return;
+    }
+
+    if header[4] != 2 {
+        issues.push(CheckIssue::new(
+            format!("{} {:?} is a 32-bit binary", what, path),
+            "use a 64-bit ROM build",
+        ));
+    }
+
+    // e_machine is stored in the byte order given by EI_DATA
+    let machine = if header[5] == 2 {
+        u16::from_be_bytes([header[18], header[19]])
+    } else {
+        u16::from_le_bytes([header[18], header[19]])
+    };
+    if machine != HOST_ELF_MACHINE {
+        issues.push(CheckIssue::new(
+            format!("{} {:?} is built for ELF machine {}, host is {}", what, path, machine, HOST_ELF_MACHINE),
+            "use a ROM built for the host architecture",
+        ));
+    }
+
+    match std::fs::metadata(path) {
+        Ok(meta) if meta.permissions().mode() & 0o111 == 0 => {
+            issues.push(CheckIssue::new(
+                format!("{} {:?} is not executable", what, path),
+                "chmod +x the file, the archive lost its permission bits",
+            ));
+        }
+        _ => {}
+    }
+}
+
+/// Read a property from a build.prop style file
+fn read_prop(path: &Path, key: &str) -> Option<String> {
+    let content = std::fs::read_to_string(path).ok()?;
+    content
+        .lines()
+        .filter_map(|line| line.split_once('='))
+        .find(|(k, _)| k.trim() == key)
+        .map(|(_, v)| v.trim().to_string())
+}
+
+/// Check a rootfs, and optionally the loader it will be started with
+///
+/// Returns every problem found; an empty list means the rootfs looks bootable.
+pub fn check_rootfs(rootfs: &str, loader: Option<&str>) -> Vec<CheckIssue> {
+    let mut issues = Vec::new();
+    let root = Path::new(rootfs);
+
+    if !root.is_dir() {
+        issues.push(CheckIssue::new(
+            format!("rootfs {:?} does not exist or is not a directory", root),
+            "import a ROM first, or pass the right path with -r",
+        ));
+        return issues;
+    }
+
+    check_elf(&root.join("init"), "init", &mut issues);
+
+    if let Some(loader) = loader {
+        check_elf(Path::new(loader), "loader", &mut issues);
+    }
+
+    for dir in REQUIRED_DIRS {
+        if !root.join(dir).is_dir() {
+            issues.push(CheckIssue::new(
+                format!("required directory /{} is missing", dir),
+                "the ROM archive is incomplete, re-import it",
+            ));
+        }
+    }
+
+    for dir in WRITABLE_DIRS {
+        let path = root.join(dir);
+        let writable = CString::new(path.to_string_lossy().as_bytes())
+            .map(|p| unsafe { libc::access(p.as_ptr(), libc::W_OK) } == 0)
+            .unwrap_or(false);
+        if !writable {
+            issues.push(CheckIssue::new(
+                format!("directory /{} is missing or not writable", dir),
+                "create it and make sure it is owned by the app user",
+            ));
+        }
+    }
+
+    let build_prop = root.join("system/build.prop");
+    match read_prop(&build_prop, "ro.product.cpu.abi") {
+        Some(abi) if abi != HOST_ABI => {
+            issues.push(CheckIssue::new(
+                format!("ROM ABI is {}, host is {}", abi, HOST_ABI),
+                "use a ROM built for the host architecture",
+            ));
+        }
+        Some(_) => {}
+        None => {
+            issues.push(CheckIssue::new(
+                format!("cannot read ro.product.cpu.abi from {:?}", build_prop),
+                "the system partition is missing or incomplete",
+            ));
+        }
+    }
+
+    issues
+}
+
+/// Parse the major and minor version from a kernel release like `5.10.43-android12`
+fn parse_kernel_version(release: &str) -> Option<(u32, u32)> {
+    let mut parts = release.split(|c: char| !c.is_ascii_digit());
+    let major = parts.next()?.parse().ok()?;
+    let minor = parts.next()?.parse().ok()?;
+    Some((major, minor))
+}
+
+/// Whether `path` can be opened for reading and writing
+fn can_open_rw(path: &str) -> bool {
+    std::fs::OpenOptions::new().read(true).write(true).open(path).is_ok()
+}
+
+/// Check the host kernel the container will run on
+///
+/// Returns every problem found; an empty list means the kernel looks usable.
+pub fn check_host() -> Vec<CheckIssue> {
+    let mut issues = Vec::new();
+
+    let mut uts: libc::utsname = unsafe { std::mem::zeroed() };
+    if unsafe { libc::uname(&mut uts) } == 0 {
+        let release = unsafe { std::ffi::CStr::from_ptr(uts.release.as_ptr()) }.to_string_lossy();
+        match parse_kernel_version(&release) {
+            Some(version) if version < MIN_KERNEL => {
+                issues.push(CheckIssue::new(
+                    format!("kernel {} is older than {}.{}", release, MIN_KERNEL.0, MIN_KERNEL.1),
+                    "the container needs a newer host kernel",
+                ));
+            }
+            Some(_) => {}
+            None => {
+                issues.push(CheckIssue::new(
+                    format!("cannot parse kernel release {:?}", release),
+                    "report the kernel release, the version check does not know its format",
+                ));
+            }
+        }
+    }
+
+    if !BINDER_DEVICES.iter().any(|path| can_open_rw(path)) {
+        issues.push(CheckIssue::new(
+            format!("none of {:?} can be opened", BINDER_DEVICES),
+            "the host kernel needs binder support (CONFIG_ANDROID_BINDER_IPC)",
+        ));
+    }
+
+    // Newer kernels drop ashmem, Android then falls back to memfd
+    let name = CString::new("twoyi-check").unwrap();
+    let memfd = unsafe { libc::syscall(libc::SYS_memfd_create, name.as_ptr(), libc::MFD_CLOEXEC) };
+    if memfd >= 0 {
+        unsafe { libc::close(memfd as libc::c_int) };
+    } else if !can_open_rw("/dev/ashmem") {
+        issues.push(CheckIssue::new(
+            "neither /dev/ashmem nor memfd_create is available".to_string(),
+            "the host kernel needs ashmem or memfd support for shared memory",
+        ));
+    }
+
+    issues
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use std::path::PathBuf;
+
+    fn scratch_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("twoyi-rootfs-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn write_elf(path: &Path, class: u8, machine: u16, mode: u32) {
+        let mut header = [0u8; 64];
+        header[..4].copy_from_slice(b"\x7fELF");
+        header[4] = class;
+        header[5] = 1;
+        header[18..20].copy_from_slice(&machine.to_le_bytes());
+        fs::write(path, header).unwrap();
+        fs::set_permissions(path, fs::Permissions::from_mode(mode)).unwrap();
+    }
+
+    fn elf_issues(path: &Path) -> Vec<String> {
+        let mut issues = Vec::new();
+        check_elf(path, "init", &mut issues);
+        assert!(issues.iter().all(|issue| !issue.hint.is_empty()));
+        issues.into_iter().map(|issue| issue.message).collect()
+    }
+
+    #[test]
+    fn test_check_elf() {
+        let dir = scratch_dir("elf");
+        let path = dir.join("init");
+
+        write_elf(&path, 2, HOST_ELF_MACHINE, 0o755);
+        assert!(elf_issues(&path).is_empty());
+
+        write_elf(&path, 1, HOST_ELF_MACHINE, 0o755);
+        assert!(elf_issues(&path)[0].contains("32-bit"));
+
+        write_elf(&path, 2, HOST_ELF_MACHINE + 1, 0o755);
+        assert!(elf_issues(&path)[0].contains("ELF machine"));
+
+        write_elf(&path, 2, HOST_ELF_MACHINE, 0o644);
+        assert!(elf_issues(&path)[0].contains("not executable"));
+
+        fs::write(&path, b"#!/bin/sh\nexec true\n").unwrap();
+        assert!(elf_issues(&path)[0].contains("not an ELF"));
+
+        fs::write(&path, b"\x7fELF").unwrap();
+        assert!(elf_issues(&path)[0].contains("cannot be read"));
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_read_prop() {
+        let dir = scratch_dir("prop");
+        let path = dir.join("build.prop");
+        fs::write(&path, "# comment\nro.product.cpu.abi = arm64-v8a\nro.build.id=ABC=1\n").unwrap();
+
+        assert_eq!(read_prop(&path, "ro.product.cpu.abi").as_deref(), Some("arm64-v8a"));
+        assert_eq!(read_prop(&path, "ro.build.id").as_deref(), Some("ABC=1"));
+        assert_eq!(read_prop(&path, "ro.missing"), None);
+        assert_eq!(read_prop(&dir.join("missing.prop"), "ro.product.cpu.abi"), None);
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_check_rootfs() {
+        let dir = scratch_dir("rootfs");
+        let root = dir.to_str().unwrap();
+
+        assert_eq!(check_rootfs(&format!("{}/missing", root), None).len(), 1);
+
+        write_elf(&dir.join("init"), 2, HOST_ELF_MACHINE, 0o755);
+        for sub in ["system", "vendor", "data", "dev/input", "dev/socket"] {
+            fs::create_dir_all(dir.join(sub)).unwrap();
+        }
+        fs::write(dir.join("system/build.prop"), format!("ro.product.cpu.abi={}\n", HOST_ABI)).unwrap();
+        let issues: Vec<String> = check_rootfs(root, None).into_iter().map(|issue| issue.message).collect();
+        assert!(issues.is_empty(), "{:?}", issues);
+
+        fs::remove_dir(dir.join("vendor")).unwrap();
+        fs::write(dir.join("system/build.prop"), "ro.product.cpu.abi=mips\n").unwrap();
+        let issues: Vec<String> = check_rootfs(root, None).into_iter().map(|issue| issue.message).collect();
+        assert_eq!(issues.len(), 2, "{:?}", issues);
+        assert!(issues.iter().any(|issue| issue.contains("/vendor")));
+        assert!(issues.iter().any(|issue| issue.contains("mips")));
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_parse_kernel_version() {
+        assert_eq!(parse_kernel_version("5.10.43-android12-9-g1234"), Some((5, 10)));
+        assert_eq!(parse_kernel_version("4.4"), Some((4, 4)));
+        assert_eq!(parse_kernel_version("3.18.140+"), Some((3, 18)));
+        assert_eq!(parse_kernel_version("6"), None);
+        assert_eq!(parse_kernel_version("custom"), None);
+        assert!(parse_kernel_version("3.10.0").unwrap() < MIN_KERNEL);
+    }
+}
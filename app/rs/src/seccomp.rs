@@ -0,0 +1,239 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+//! Seccomp-bpf profiles for the container process
+//!
+//! The filter is built in the parent and installed in the forked child right
+//! before exec of `./init`, so it applies to every process in the container.
+//! Denied syscalls fail with EPERM instead of killing the caller, which is
+//! what Android code already expects from an unprivileged app sandbox.
+//!
+//! The permissive profile is the default and fails open: if it can't be
+//! installed the container starts unfiltered. Only the strict profile refuses
+//! to start without its filter.
+//!
+//! Syscall numbers are only known for the native ABI, so compat-ABI callers
+//! (32-bit arm on an arm64 host) bypass the filter entirely.
+
+use std::io;
+use std::sync::Mutex;
+use once_cell::sync::Lazy;
+
+/// Syscall filtering applied to the container
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SeccompProfile {
+    Disabled,
+    /// Deny syscalls that can take down or reconfigure the host kernel
+    Permissive,
+    /// Additionally deny mount table, clock and accounting changes
+    Strict,
+}
+
+impl SeccompProfile {
+    pub fn from_int(value: i32) -> Option<Self> {
+        match value {
+            0 => Some(SeccompProfile::Disabled),
+            1 => Some(SeccompProfile::Permissive),
+            2 => Some(SeccompProfile::Strict),
+            _ => None,
+        }
+    }
+}
+
+/// Global seccomp profile setting, permissive unless the profile opts out
+static PROFILE: Lazy<Mutex<SeccompProfile>> = Lazy::new(|| Mutex::new(SeccompProfile::Permissive));
+
+pub fn set_profile(profile: SeccompProfile) {
+    log::info!("[SECCOMP] Profile set to: {:?}", profile);
+    *PROFILE.lock().unwrap() = profile;
+}
+
+pub fn profile() -> SeccompProfile {
+    *PROFILE.lock().unwrap()
+}
+
+const PERMISSIVE_DENY: &[libc::c_long] = &[
+    libc::SYS_reboot,
+    libc::SYS_kexec_load,
+    libc::SYS_init_module,
+    libc::SYS_finit_module,
+    libc::SYS_delete_module,
+    libc::SYS_swapon,
+    libc::SYS_swapoff,
+];
+
+const STRICT_DENY: &[libc::c_long] = &[
+    libc::SYS_mount,
+    libc::SYS_umount2,
+    libc::SYS_pivot_root,
+    libc::SYS_acct,
+    libc::SYS_settimeofday,
+    libc::SYS_clock_settime,
+    libc::SYS_clock_adjtime,
+    libc::SYS_adjtimex,
+];
+
+#[cfg(target_arch = "aarch64")]
+const AUDIT_ARCH_NATIVE: u32 = 0xC000_00B7;
+#[cfg(target_arch = "x86_64")]
+const AUDIT_ARCH_NATIVE: u32 = 0xC000_003E;
+
+const BPF_LD_W_ABS: u16 = 0x20;
+const BPF_JEQ_K: u16 = 0x15;
+const BPF_RET_K: u16 = 0x06;
+
+const SECCOMP_RET_ALLOW: u32 = 0x7fff_0000;
+const SECCOMP_RET_ERRNO: u32 = 0x0005_0000;
+
+/// Offsets into `struct seccomp_data`
+const SECCOMP_DATA_NR: u32 = 0;
+const SECCOMP_DATA_ARCH: u32 = 4;
+
+/// Not exported by libc for Android
+const PR_SET_SECCOMP: libc::c_int = 22;
+const PR_SET_NO_NEW_PRIVS: libc::c_int = 38;
+const SECCOMP_MODE_FILTER: libc::c_ulong = 2;
+
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct SockFilter {
+    code: u16,
+    jt: u8,
+    jf: u8,
+    k: u32,
+}
+
+#[repr(C)]
+struct SockFprog {
+    len: u16,
+    filter: *const SockFilter,
+}
+
+fn stmt(code: u16, k: u32) -> SockFilter {
+    SockFilter { code, jt: 0, jf: 0, k }
+}
+
+fn jump(code: u16, k: u32, jt: u8, jf: u8) -> SockFilter {
+    SockFilter { code, jt, jf, k }
+}
+
+/// A compiled filter, ready to be installed after fork
+pub struct SeccompFilter {
+    program: Vec<SockFilter>,
+}
+
+impl SeccompFilter {
+    /// Build the filter for a profile, `None` when filtering is disabled
+    pub fn build(profile: SeccompProfile) -> Option<Self> {
+        let denied: Vec<libc::c_long> = match profile {
+            SeccompProfile::Disabled => return None,
+            SeccompProfile::Permissive => PERMISSIVE_DENY.to_vec(),
+            SeccompProfile::Strict => PERMISSIVE_DENY.iter().chain(STRICT_DENY).copied().collect(),
+        };
+
+        let mut program = Vec::with_capacity(denied.len() * 2 + 5);
+        // Syscall numbers are per-ABI; compat (32-bit) callers are let through
+        // since this table only knows native numbers.
+        program.push(stmt(BPF_LD_W_ABS, SECCOMP_DATA_ARCH));
+        program.push(jump(BPF_JEQ_K, AUDIT_ARCH_NATIVE, 1, 0));
+        program.push(stmt(BPF_RET_K, SECCOMP_RET_ALLOW));
+        program.push(stmt(BPF_LD_W_ABS, SECCOMP_DATA_NR));
+        for nr in denied {
+            program.push(jump(BPF_JEQ_K, nr as u32, 0, 1));
+            program.push(stmt(BPF_RET_K, SECCOMP_RET_ERRNO | libc::EPERM as u32));
+        }
+        program.push(stmt(BPF_RET_K, SECCOMP_RET_ALLOW));
+
+        Some(SeccompFilter { program })
+    }
+
+    /// Install the filter on the calling thread
+    ///
+    /// Only async-signal-safe calls are made, so this may run between fork
+    /// and exec.
+    pub fn install(&self) -> io::Result<()> {
+        let prog = SockFprog {
+            len: self.program.len() as u16,
+            filter: self.program.as_ptr(),
+        };
+        unsafe {
+            if libc::prctl(PR_SET_NO_NEW_PRIVS, 1, 0, 0, 0) != 0 {
+                return Err(io::Error::last_os_error());
+            }
+            if libc::prctl(PR_SET_SECCOMP, SECCOMP_MODE_FILTER, &prog as *const SockFprog) != 0 {
+                return Err(io::Error::last_os_error());
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Run the classic BPF subset the filter uses against one syscall
+    fn run(filter: &SeccompFilter, arch: u32, nr: u32) -> u32 {
+        let mut acc = 0;
+        let mut pc = 0;
+        loop {
+            let insn = filter.program[pc];
+            match insn.code {
+                BPF_LD_W_ABS => match insn.k {
+                    SECCOMP_DATA_NR => acc = nr,
+                    SECCOMP_DATA_ARCH => acc = arch,
+                    k => panic!("unexpected load offset {}", k),
+                },
+                BPF_JEQ_K => {
+                    pc += if acc == insn.k { insn.jt } else { insn.jf } as usize;
+                }
+                BPF_RET_K => return insn.k,
+                code => panic!("unexpected opcode {:#x}", code),
+            }
+            pc += 1;
+        }
+    }
+
+    const DENIED: u32 = SECCOMP_RET_ERRNO | libc::EPERM as u32;
+
+    #[test]
+    fn test_disabled_builds_no_filter() {
+        assert!(SeccompFilter::build(SeccompProfile::Disabled).is_none());
+    }
+
+    #[test]
+    fn test_permissive_filter() {
+        let filter = SeccompFilter::build(SeccompProfile::Permissive).unwrap();
+        assert_eq!(filter.program.len(), PERMISSIVE_DENY.len() * 2 + 5);
+        for &nr in PERMISSIVE_DENY {
+            assert_eq!(run(&filter, AUDIT_ARCH_NATIVE, nr as u32), DENIED);
+        }
+        for &nr in STRICT_DENY {
+            assert_eq!(run(&filter, AUDIT_ARCH_NATIVE, nr as u32), SECCOMP_RET_ALLOW);
+        }
+        assert_eq!(run(&filter, AUDIT_ARCH_NATIVE, libc::SYS_read as u32), SECCOMP_RET_ALLOW);
+    }
+
+    #[test]
+    fn test_strict_filter() {
+        let filter = SeccompFilter::build(SeccompProfile::Strict).unwrap();
+        for &nr in PERMISSIVE_DENY.iter().chain(STRICT_DENY) {
+            assert_eq!(run(&filter, AUDIT_ARCH_NATIVE, nr as u32), DENIED);
+        }
+        assert_eq!(run(&filter, AUDIT_ARCH_NATIVE, libc::SYS_openat as u32), SECCOMP_RET_ALLOW);
+    }
+
+    #[test]
+    fn test_foreign_arch_is_allowed() {
+        let filter = SeccompFilter::build(SeccompProfile::Strict).unwrap();
+        assert_eq!(run(&filter, 0x4000_0028, libc::SYS_reboot as u32), SECCOMP_RET_ALLOW);
+    }
+
+    #[test]
+    fn test_profile_from_int() {
+        assert_eq!(SeccompProfile::from_int(0), Some(SeccompProfile::Disabled));
+        assert_eq!(SeccompProfile::from_int(2), Some(SeccompProfile::Strict));
+        assert_eq!(SeccompProfile::from_int(3), None);
+    }
+}
@@ -0,0 +1,126 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+
+package io.twoyi;
+
+import android.util.SparseIntArray;
+import android.view.InputDevice;
+import android.view.KeyEvent;
+import android.view.MotionEvent;
+
+/**
+ * Forwards a physical gamepad to the container's virtual gamepad.
+ *
+ * Android key codes and axes are translated to the linux codes the
+ * guest's evdev device advertises.
+ */
+public final class GamepadInput {
+
+    private static final int BTN_SOUTH = 0x130;
+    private static final int BTN_EAST = 0x131;
+    private static final int BTN_NORTH = 0x133;
+    private static final int BTN_WEST = 0x134;
+    private static final int BTN_TL = 0x136;
+    private static final int BTN_TR = 0x137;
+    private static final int BTN_TL2 = 0x138;
+    private static final int BTN_TR2 = 0x139;
+    private static final int BTN_SELECT = 0x13a;
+    private static final int BTN_START = 0x13b;
+    private static final int BTN_MODE = 0x13c;
+    private static final int BTN_THUMBL = 0x13d;
+    private static final int BTN_THUMBR = 0x13e;
+
+    private static final int ABS_X = 0x00;
+    private static final int ABS_Y = 0x01;
+    private static final int ABS_Z = 0x02;
+    private static final int ABS_RZ = 0x05;
+    private static final int ABS_GAS = 0x09;
+    private static final int ABS_BRAKE = 0x0a;
+    private static final int ABS_HAT0X = 0x10;
+    private static final int ABS_HAT0Y = 0x11;
+
+    private static final int STICK_MAX = 32767;
+    private static final int TRIGGER_MAX = 255;
+
+    private static final SparseIntArray BUTTONS = new SparseIntArray();
+
+    static {
+        BUTTONS.put(KeyEvent.KEYCODE_BUTTON_A, BTN_SOUTH);
+        BUTTONS.put(KeyEvent.KEYCODE_BUTTON_B, BTN_EAST);
+        BUTTONS.put(KeyEvent.KEYCODE_BUTTON_X, BTN_NORTH);
+        BUTTONS.put(KeyEvent.KEYCODE_BUTTON_Y, BTN_WEST);
+        BUTTONS.put(KeyEvent.KEYCODE_BUTTON_L1, BTN_TL);
+        BUTTONS.put(KeyEvent.KEYCODE_BUTTON_R1, BTN_TR);
+        BUTTONS.put(KeyEvent.KEYCODE_BUTTON_L2, BTN_TL2);
+        BUTTONS.put(KeyEvent.KEYCODE_BUTTON_R2, BTN_TR2);
+        BUTTONS.put(KeyEvent.KEYCODE_BUTTON_SELECT, BTN_SELECT);
+        BUTTONS.put(KeyEvent.KEYCODE_BUTTON_START, BTN_START);
+        BUTTONS.put(KeyEvent.KEYCODE_BUTTON_MODE, BTN_MODE);
+        BUTTONS.put(KeyEvent.KEYCODE_BUTTON_THUMBL, BTN_THUMBL);
+        BUTTONS.put(KeyEvent.KEYCODE_BUTTON_THUMBR, BTN_THUMBR);
+    }
+
+    private GamepadInput() {
+    }
+
+    private static boolean isGamepad(int source) {
+        return (source & InputDevice.SOURCE_GAMEPAD) == InputDevice.SOURCE_GAMEPAD
+                || (source & InputDevice.SOURCE_JOYSTICK) == InputDevice.SOURCE_JOYSTICK;
+    }
+
+    /**
+     * @return true if the event came from a gamepad and was forwarded
+     */
+    public static boolean handleKeyEvent(KeyEvent event) {
+        if (!isGamepad(event.getSource())) {
+            return false;
+        }
+        int button = BUTTONS.get(event.getKeyCode(), -1);
+        if (button < 0) {
+            return false;
+        }
+        if (event.getRepeatCount() > 0) {
+            return true;
+        }
+        int action = event.getAction();
+        if (action == KeyEvent.ACTION_DOWN) {
+            Renderer.sendGamepadButton(button, 1);
+        } else if (action == KeyEvent.ACTION_UP) {
+            Renderer.sendGamepadButton(button, 0);
+        }
+        return true;
+    }
+
+    /**
+     * @return true if the event came from a gamepad and was forwarded
+     */
+    public static boolean handleMotionEvent(MotionEvent event) {
+        if (!isGamepad(event.getSource()) || event.getAction() != MotionEvent.ACTION_MOVE) {
+            return false;
+        }
+
+        // Controllers report triggers on either pair of axes
+        float left = Math.max(event.getAxisValue(MotionEvent.AXIS_LTRIGGER), event.getAxisValue(MotionEvent.AXIS_BRAKE));
+        float right = Math.max(event.getAxisValue(MotionEvent.AXIS_RTRIGGER), event.getAxisValue(MotionEvent.AXIS_GAS));
+
+        // Same layout as Generic.kl, so the guest maps the axes back one to one
+        Renderer.sendGamepadAxes(new int[]{
+                ABS_X, stick(event.getAxisValue(MotionEvent.AXIS_X)),
+                ABS_Y, stick(event.getAxisValue(MotionEvent.AXIS_Y)),
+                ABS_Z, stick(event.getAxisValue(MotionEvent.AXIS_Z)),
+                ABS_RZ, stick(event.getAxisValue(MotionEvent.AXIS_RZ)),
+                ABS_BRAKE, Math.round(left * TRIGGER_MAX),
+                ABS_GAS, Math.round(right * TRIGGER_MAX),
+                ABS_HAT0X, Math.round(event.getAxisValue(MotionEvent.AXIS_HAT_X)),
+                ABS_HAT0Y, Math.round(event.getAxisValue(MotionEvent.AXIS_HAT_Y)),
+        });
+        return true;
+    }
+
+    private static int stick(float value) {
+        return Math.round(value * STICK_MAX);
+    }
+}
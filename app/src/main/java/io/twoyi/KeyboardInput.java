@@ -0,0 +1,64 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+
+package io.twoyi;
+
+import android.view.InputDevice;
+import android.view.KeyEvent;
+
+/**
+ * Forwards a host hardware keyboard to the container's virtual keyboard.
+ *
+ * Hardware keys carry the linux scan code they were reported with, so no
+ * keycode translation is needed. Presses and releases are forwarded as-is;
+ * the native side synthesizes auto-repeat while a key is held.
+ */
+public final class KeyboardInput {
+
+    private KeyboardInput() {
+    }
+
+    private static boolean isFullKeyboard(InputDevice device) {
+        return device != null
+                && !device.isVirtual()
+                && device.getKeyboardType() == InputDevice.KEYBOARD_TYPE_ALPHABETIC;
+    }
+
+    private static boolean isFullKeyboard(KeyEvent event) {
+        return isFullKeyboard(event.getDevice());
+    }
+
+    /**
+     * @return true if a physical keyboard with letter keys is connected to the host
+     */
+    public static boolean hasFullKeyboard() {
+        for (int id : InputDevice.getDeviceIds()) {
+            if (isFullKeyboard(InputDevice.getDevice(id))) {
+                return true;
+            }
+        }
+        return false;
+    }
+
+    /**
+     * @return true if the event came from a hardware keyboard and was forwarded
+     */
+    public static boolean handleKeyEvent(KeyEvent event) {
+        if (!isFullKeyboard(event) || event.getScanCode() <= 0) {
+            return false;
+        }
+        if (event.getRepeatCount() > 0) {
+            return true;
+        }
+        int action = event.getAction();
+        if (action == KeyEvent.ACTION_DOWN) {
+            Renderer.sendKey(event.getScanCode(), 1);
+        } else if (action == KeyEvent.ACTION_UP) {
+            Renderer.sendKey(event.getScanCode(), 0);
+        }
+        return true;
+    }
+}
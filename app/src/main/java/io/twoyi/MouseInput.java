@@ -0,0 +1,153 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+
+package io.twoyi;
+
+import android.view.InputDevice;
+import android.view.MotionEvent;
+
+/**
+ * Forwards hover, scroll and button changes of a host mouse to the
+ * container's virtual mouse.
+ *
+ * Primary clicks and drags keep arriving as touches through
+ * {@link Renderer#handleTouch}; this only covers what touch cannot express.
+ *
+ * The virtual mouse is relative, so the guest pointer is re-homed whenever
+ * the host pointer enters the surface: slammed into the top-left corner, then
+ * moved by the absolute position. Buttons and wheel then land under the host
+ * pointer as long as the guest applies no pointer acceleration.
+ */
+public final class MouseInput {
+
+    private static final int BUTTON_LEFT = 1;
+    private static final int BUTTON_RIGHT = 1 << 1;
+    private static final int BUTTON_MIDDLE = 1 << 2;
+    private static final int BUTTON_BACK = 1 << 3;
+    private static final int BUTTON_FORWARD = 1 << 4;
+
+    // Larger than any virtual display, so the guest pointer clamps at its edge
+    private static final int HOME_DELTA = -(1 << 15);
+
+    // Last position sent to the guest, in virtual display pixels
+    private int mLastX;
+    private int mLastY;
+    private boolean mHomed;
+
+    // Sub-pixel motion carried over between captured events
+    private float mCapturedRemainderX;
+    private float mCapturedRemainderY;
+
+    private static int toButtons(int buttonState) {
+        int buttons = 0;
+        if ((buttonState & MotionEvent.BUTTON_PRIMARY) != 0) buttons |= BUTTON_LEFT;
+        if ((buttonState & MotionEvent.BUTTON_SECONDARY) != 0) buttons |= BUTTON_RIGHT;
+        if ((buttonState & MotionEvent.BUTTON_TERTIARY) != 0) buttons |= BUTTON_MIDDLE;
+        if ((buttonState & MotionEvent.BUTTON_BACK) != 0) buttons |= BUTTON_BACK;
+        if ((buttonState & MotionEvent.BUTTON_FORWARD) != 0) buttons |= BUTTON_FORWARD;
+        return buttons;
+    }
+
+    /**
+     * @param scaleX surface to virtual display scale
+     * @param scaleY surface to virtual display scale
+     * @return true if the event came from a mouse and was forwarded
+     */
+    public boolean handleMotionEvent(MotionEvent event, float scaleX, float scaleY) {
+        if (!event.isFromSource(InputDevice.SOURCE_MOUSE)) {
+            return false;
+        }
+
+        int buttons = toButtons(event.getButtonState());
+        switch (event.getActionMasked()) {
+            case MotionEvent.ACTION_HOVER_ENTER:
+                rehome(event, scaleX, scaleY, buttons);
+                return true;
+            case MotionEvent.ACTION_HOVER_MOVE: {
+                if (!mHomed) {
+                    rehome(event, scaleX, scaleY, buttons);
+                    return true;
+                }
+                int x = Math.round(event.getX() * scaleX);
+                int y = Math.round(event.getY() * scaleY);
+                Renderer.sendMouseEvent(x - mLastX, y - mLastY, 0, 0, buttons);
+                mLastX = x;
+                mLastY = y;
+                return true;
+            }
+            case MotionEvent.ACTION_HOVER_EXIT:
+                mHomed = false;
+                return true;
+            case MotionEvent.ACTION_SCROLL: {
+                int wheel = Math.round(event.getAxisValue(MotionEvent.AXIS_VSCROLL));
+                int hwheel = Math.round(event.getAxisValue(MotionEvent.AXIS_HSCROLL));
+                Renderer.sendMouseEvent(0, 0, wheel, hwheel, buttons);
+                return true;
+            }
+            case MotionEvent.ACTION_BUTTON_PRESS:
+            case MotionEvent.ACTION_BUTTON_RELEASE:
+                // The primary button is delivered as a touch
+                Renderer.sendMouseEvent(0, 0, 0, 0, buttons & ~BUTTON_LEFT);
+                return true;
+            default:
+                return false;
+        }
+    }
+
+    /**
+     * Move the guest pointer to the event's position: into the top-left corner
+     * first, then by the absolute offset
+     */
+    private void rehome(MotionEvent event, float scaleX, float scaleY, int buttons) {
+        mLastX = Math.round(event.getX() * scaleX);
+        mLastY = Math.round(event.getY() * scaleY);
+        Renderer.sendMouseEvent(HOME_DELTA, HOME_DELTA, 0, 0, buttons);
+        Renderer.sendMouseEvent(mLastX, mLastY, 0, 0, buttons);
+        mHomed = true;
+    }
+
+    /**
+     * Forward an event delivered while the host pointer is captured.
+     * Captured events carry relative motion and every button, including primary.
+     * @return true if the event was forwarded
+     */
+    public boolean handleCapturedPointerEvent(MotionEvent event) {
+        int buttons = toButtons(event.getButtonState());
+        switch (event.getActionMasked()) {
+            case MotionEvent.ACTION_SCROLL: {
+                int wheel = Math.round(event.getAxisValue(MotionEvent.AXIS_VSCROLL));
+                int hwheel = Math.round(event.getAxisValue(MotionEvent.AXIS_HSCROLL));
+                Renderer.sendMouseEvent(0, 0, wheel, hwheel, buttons);
+                return true;
+            }
+            default: {
+                float x = mCapturedRemainderX + event.getX();
+                float y = mCapturedRemainderY + event.getY();
+                for (int i = 0; i < event.getHistorySize(); i++) {
+                    x += event.getHistoricalX(i);
+                    y += event.getHistoricalY(i);
+                }
+                int dx = (int) x;
+                int dy = (int) y;
+                mCapturedRemainderX = x - dx;
+                mCapturedRemainderY = y - dy;
+                Renderer.sendMouseEvent(dx, dy, 0, 0, buttons);
+                return true;
+            }
+        }
+    }
+
+    /**
+     * Release every button, e.g. when pointer capture is lost mid-drag
+     */
+    public void releaseCapturedButtons() {
+        mCapturedRemainderX = 0;
+        mCapturedRemainderY = 0;
+        // Captured motion moved the guest pointer, so the next hover re-homes it
+        mHomed = false;
+        Renderer.sendMouseEvent(0, 0, 0, 0, 0);
+    }
+}
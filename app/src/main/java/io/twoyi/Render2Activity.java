@@ -22,6 +22,8 @@ import android.app.Activity;
 import android.app.ProgressDialog;
 import android.content.ContentResolver;
 import android.content.Intent;
+import android.graphics.Bitmap;
+import android.hardware.input.InputManager;
 import android.net.Uri;
 import android.os.Bundle;
 import android.os.SystemClock;
@@ -41,6 +43,7 @@ import android.widget.TextView;
 import android.widget.Toast;
 
 import androidx.annotation.NonNull;
+import androidx.core.content.FileProvider;
 
 import com.cleveroad.androidmanimation.LoadingAnimationView;
 
@@ -48,6 +51,8 @@ import java.io.File;
 import java.io.FileOutputStream;
 import java.io.InputStream;
 import java.io.OutputStream;
+import java.util.Locale;
+import java.util.TimeZone;
 import java.util.concurrent.TimeUnit;
 import java.util.concurrent.atomic.AtomicBoolean;
 
@@ -57,6 +62,7 @@ import io.twoyi.utils.NavUtils;
 import io.twoyi.utils.ProfileManager;
 import io.twoyi.utils.ProfileSettings;
 import io.twoyi.utils.RomManager;
+import io.twoyi.utils.ScreenshotUtils;
 import io.twoyi.utils.UIHelper;
 
 /**
@@ -87,6 +93,32 @@ public class Render2Activity extends Activity implements View.OnTouchListener {
 
     private final AtomicBoolean mIsExtracting = new AtomicBoolean(false);
 
+    private final MouseInput mMouseInput = new MouseInput();
+
+    private final InputManager.InputDeviceListener mInputDeviceListener = new InputManager.InputDeviceListener() {
+        @Override
+        public void onInputDeviceAdded(int deviceId) {
+            updatePhysicalKeyboard();
+        }
+
+        @Override
+        public void onInputDeviceRemoved(int deviceId) {
+            updatePhysicalKeyboard();
+        }
+
+        @Override
+        public void onInputDeviceChanged(int deviceId) {
+            updatePhysicalKeyboard();
+        }
+    };
+
+    private final Renderer.EventListener mRendererEventListener = (event, detail) -> {
+        Log.w(TAG, "native event: " + event + " (" + detail + ")");
+        if (Renderer.EVENT_CONTAINER_EXITED.equals(event)) {
+            runOnUiThread(() -> Toast.makeText(getApplicationContext(), "Container exited: " + detail, Toast.LENGTH_SHORT).show());
+        }
+    };
+
     private final SurfaceHolder.Callback mSurfaceCallback = new SurfaceHolder.Callback() {
         @Override
         public void surfaceCreated(@NonNull SurfaceHolder holder) {
@@ -113,6 +145,16 @@ public class Render2Activity extends Activity implements View.OnTouchListener {
                 Log.i(TAG, "Debug renderer: disabled");
             }
             
+            Renderer.setEventListener(mRendererEventListener);
+
+            // Propagate host timezone and locale into the container
+            if (ProfileSettings.isSyncHostTimeEnabled(getApplicationContext())) {
+                Renderer.setHostTimeSettings(TimeZone.getDefault().getID(), Locale.getDefault().toLanguageTag());
+            }
+
+            // Syscall filtering is opt-in, 1 selects the permissive profile
+            Renderer.setSeccompProfile(ProfileSettings.isSeccompFilterEnabled(getApplicationContext()) ? 1 : 0);
+
             // Calculate proper DPI based on physical screen and virtual display scaling
             WindowManager windowManager = getWindowManager();
             Display defaultDisplay = windowManager.getDefaultDisplay();
@@ -194,6 +236,8 @@ public class Render2Activity extends Activity implements View.OnTouchListener {
         UITips.checkForAndroid12(this, this::bootSystem);
 
         mSurfaceView.setOnTouchListener(this);
+        mSurfaceView.setFocusable(true);
+        mSurfaceView.setOnCapturedPointerListener((view, event) -> mMouseInput.handleCapturedPointerEvent(event));
 
     }
 
@@ -365,6 +409,9 @@ public class Render2Activity extends Activity implements View.OnTouchListener {
 
         if (hasFocus) {
             NavUtils.hideNavigation(getWindow());
+        } else {
+            // Keys released while another window has focus never reach us
+            Renderer.releaseAllKeys();
         }
 
         // Update global visibility.
@@ -373,6 +420,15 @@ public class Render2Activity extends Activity implements View.OnTouchListener {
 
     @Override
     public boolean onTouch(View v, MotionEvent event) {
+        // Scale from surface dimensions to virtual display dimensions
+        // No translation needed since coordinates are already relative to SurfaceView
+        float scaleX = (float) mVirtualDisplayWidth / mSurfaceWidth;
+        float scaleY = (float) mVirtualDisplayHeight / mSurfaceHeight;
+
+        if (StylusInput.handleMotionEvent(event, scaleX, scaleY)) {
+            return true;
+        }
+
         // Transform touch coordinates from surface space to virtual display space
         // Note: event coordinates are already relative to the SurfaceView (not screen)
         // since the touch listener is attached to mSurfaceView
@@ -380,11 +436,6 @@ public class Render2Activity extends Activity implements View.OnTouchListener {
         
         // Calculate the transformation matrix
         android.graphics.Matrix matrix = new android.graphics.Matrix();
-        
-        // Scale from surface dimensions to virtual display dimensions
-        // No translation needed since coordinates are already relative to SurfaceView
-        float scaleX = (float) mVirtualDisplayWidth / mSurfaceWidth;
-        float scaleY = (float) mVirtualDisplayHeight / mSurfaceHeight;
         matrix.postScale(scaleX, scaleY);
         
         // Transform the event
@@ -395,6 +446,137 @@ public class Render2Activity extends Activity implements View.OnTouchListener {
         return true;
     }
 
+    /**
+     * Ctrl+Alt+G grabs or releases the host mouse, as in QEMU
+     */
+    private static boolean isPointerCaptureToggle(KeyEvent event) {
+        return event.getKeyCode() == KeyEvent.KEYCODE_G && event.isCtrlPressed() && event.isAltPressed();
+    }
+
+    /**
+     * Ctrl+Alt+S captures the container display and offers to share it
+     */
+    private static boolean isScreenshotShortcut(KeyEvent event) {
+        return event.getKeyCode() == KeyEvent.KEYCODE_S && event.isCtrlPressed() && event.isAltPressed();
+    }
+
+    private void shareScreenshot() {
+        ScreenshotUtils.capture(mSurfaceView, bitmap -> {
+            if (bitmap == null) {
+                Toast.makeText(this, R.string.screenshot_failed, Toast.LENGTH_SHORT).show();
+                return;
+            }
+            new Thread(() -> {
+                File screenshot = new File(getCacheDir(), "screenshot.png");
+                try (OutputStream out = new FileOutputStream(screenshot)) {
+                    bitmap.compress(Bitmap.CompressFormat.PNG, 100, out);
+                } catch (Throwable e) {
+                    Log.e(TAG, "save screenshot failed", e);
+                    runOnUiThread(() -> Toast.makeText(this, R.string.screenshot_failed, Toast.LENGTH_SHORT).show());
+                    return;
+                } finally {
+                    bitmap.recycle();
+                }
+
+                Uri uri = FileProvider.getUriForFile(this, "io.twoyi.fileprovider", screenshot);
+                Intent shareIntent = new Intent(Intent.ACTION_SEND);
+                shareIntent.putExtra(Intent.EXTRA_STREAM, uri);
+                shareIntent.setDataAndType(uri, "image/png");
+                shareIntent.addFlags(Intent.FLAG_GRANT_READ_URI_PERMISSION);
+                runOnUiThread(() -> startActivity(Intent.createChooser(shareIntent, getString(R.string.screenshot_share_title))));
+            }).start();
+        });
+    }
+
+    private void togglePointerCapture() {
+        if (mSurfaceView.hasPointerCapture()) {
+            mSurfaceView.releasePointerCapture();
+        } else {
+            mSurfaceView.requestFocus();
+            mSurfaceView.requestPointerCapture();
+        }
+    }
+
+    @Override
+    public void onPointerCaptureChanged(boolean hasCapture) {
+        super.onPointerCaptureChanged(hasCapture);
+        Log.i(TAG, "pointer capture: " + hasCapture);
+        if (!hasCapture) {
+            mMouseInput.releaseCapturedButtons();
+        }
+    }
+
+    @Override
+    protected void onResume() {
+        super.onResume();
+        InputManager inputManager = (InputManager) getSystemService(INPUT_SERVICE);
+        inputManager.registerInputDeviceListener(mInputDeviceListener, null);
+        updatePhysicalKeyboard();
+    }
+
+    @Override
+    protected void onPause() {
+        super.onPause();
+        InputManager inputManager = (InputManager) getSystemService(INPUT_SERVICE);
+        inputManager.unregisterInputDeviceListener(mInputDeviceListener);
+    }
+
+    private void updatePhysicalKeyboard() {
+        Renderer.setPhysicalKeyboard(KeyboardInput.hasFullKeyboard() ? 1 : 0);
+    }
+
+    @Override
+    protected void onDestroy() {
+        super.onDestroy();
+        Renderer.setEventListener(null);
+        Renderer.destroy();
+    }
+
+    @Override
+    public boolean dispatchKeyEvent(KeyEvent event) {
+        if (isPointerCaptureToggle(event)) {
+            if (event.getAction() == KeyEvent.ACTION_DOWN && event.getRepeatCount() == 0) {
+                togglePointerCapture();
+            }
+            return true;
+        }
+        if (isScreenshotShortcut(event)) {
+            if (event.getAction() == KeyEvent.ACTION_DOWN && event.getRepeatCount() == 0) {
+                shareScreenshot();
+            }
+            return true;
+        }
+        if (GamepadInput.handleKeyEvent(event)) {
+            return true;
+        }
+        if (KeyboardInput.handleKeyEvent(event)) {
+            return true;
+        }
+        if (event.getKeyCode() == KeyEvent.KEYCODE_BACK) {
+            // Back belongs to the container, including long presses
+            Renderer.sendKeyEvent(event.getKeyCode(), event.getAction(),
+                    event.getMetaState(), event.getRepeatCount());
+            return true;
+        }
+        return super.dispatchKeyEvent(event);
+    }
+
+    @Override
+    public boolean dispatchGenericMotionEvent(MotionEvent event) {
+        if (GamepadInput.handleMotionEvent(event)) {
+            return true;
+        }
+        float scaleX = (float) mVirtualDisplayWidth / mSurfaceWidth;
+        float scaleY = (float) mVirtualDisplayHeight / mSurfaceHeight;
+        if (StylusInput.handleMotionEvent(event, scaleX, scaleY)) {
+            return true;
+        }
+        if (mMouseInput.handleMotionEvent(event, scaleX, scaleY)) {
+            return true;
+        }
+        return super.dispatchGenericMotionEvent(event);
+    }
+
     @Override
     public boolean onKeyDown(int keyCode, KeyEvent event) {
         Log.d(TAG, "onKeyDown: " + keyCode);
@@ -404,12 +586,6 @@ public class Render2Activity extends Activity implements View.OnTouchListener {
         return super.onKeyDown(keyCode, event);
     }
 
-    @Override
-    public void onBackPressed() {
-        // super.onBackPressed();
-        Renderer.sendKeycode(KeyEvent.KEYCODE_HOME);
-    }
-
     private float getBestFps() {
         WindowManager windowManager = getWindowManager();
         Display defaultDisplay = windowManager.getDefaultDisplay();
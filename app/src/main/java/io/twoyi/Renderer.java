@@ -37,16 +37,238 @@ public class Renderer {
 
     public static native void removeWindow(Surface surface);
 
+    /**
+     * Tear down the renderer after its surface is gone so a later {@link #init} starts it afresh.
+     * The container and input devices keep running.
+     */
+    public static native void destroy();
+
     public static native void handleTouch(MotionEvent event);
 
-    public static native void sendKeycode(int keycode);
+    /**
+     * Set the number of multi-touch slots of the touch device, must be called before {@link #init}
+     * @param slots 1 to 32, defaults to 10
+     */
+    public static native void setTouchSlots(int slots);
+
+    public static final int TOUCH_SWAP_XY = 0x1;
+    public static final int TOUCH_INVERT_X = 0x2;
+    public static final int TOUCH_INVERT_Y = 0x4;
+
+    /**
+     * Map coordinates passed to {@link #handleTouch} onto the touch device.
+     * A point maps as (p - offset) * scale, then the TOUCH_* flags apply; the default is identity.
+     * @param flags TOUCH_SWAP_XY, TOUCH_INVERT_X and TOUCH_INVERT_Y
+     */
+    public static native void setTouchTransform(float offsetX, float offsetY, float scaleX, float scaleY, int flags);
+
+    /**
+     * Forward an Android key event to the container.
+     * Down events with a non-zero repeat count are dropped, held keys repeat natively.
+     * @param keycode   KeyEvent.KEYCODE_*
+     * @param action    KeyEvent.ACTION_DOWN or KeyEvent.ACTION_UP
+     * @param metaState KeyEvent.getMetaState(), the modifiers are held around the key
+     * @param repeat    KeyEvent.getRepeatCount()
+     */
+    public static native void sendKeyEvent(int keycode, int action, int metaState, int repeat);
+
+    /**
+     * Press or release a key of the virtual keyboard, a held key auto-repeats
+     * @param key linux KEY_* code
+     * @param pressed 1 to press, 0 to release
+     */
+    public static native void sendKey(int key, int pressed);
+
+    /**
+     * Press or release a key with modifiers held around it
+     * @param key linux KEY_* code
+     * @param pressed 1 to press, 0 to release
+     * @param metaState KeyEvent.META_SHIFT_ON, META_CTRL_ON, META_ALT_ON and META_META_ON bits;
+     *                  modifiers not already held are pressed with the key and released after it
+     */
+    public static native void sendKeyWithMeta(int key, int pressed, int metaState);
+
+    /**
+     * Release every key still held on the virtual keyboard
+     */
+    public static native void releaseAllKeys();
+
+    /**
+     * Offer the container a full keyboard device while a physical keyboard is connected.
+     * Without it keys still reach the container, but it keeps showing its soft keyboard.
+     * @param attached 1 when a physical keyboard is connected, 0 when the last one is gone
+     */
+    public static native void setPhysicalKeyboard(int attached);
+
+    /**
+     * Press or release a physical key of the virtual keyboard
+     * @param code key name as in the web KeyboardEvent.code, e.g. "KeyA" or "ArrowUp"
+     * @param pressed 1 to press, 0 to release
+     */
+    public static native void sendKeyByCode(String code, int pressed);
+
+    /**
+     * Press or release the key that types an X11 keysym in the container's layout
+     * @param keysym X11 keysym
+     * @param pressed 1 to press, 0 to release
+     */
+    public static native void sendKeysym(int keysym, int pressed);
+
+    /**
+     * Set the keyboard layout the container uses, for character and keysym input
+     * @param layout "us", "fr" or "de"
+     */
+    public static native void setKeyboardLayout(String layout);
+
+    /**
+     * Type a string as key presses on the virtual keyboard
+     * @param text characters without a key in the keyboard layout are skipped
+     */
+    public static native void typeText(String text);
+
+    /**
+     * Commit text from the app's own soft keyboard, typed on the virtual keyboard like {@link #typeText}
+     *
+     * Only characters in the configured keyboard layout can be committed; anything
+     * else, such as CJK or emoji, is dropped.
+     * @return the number of characters typed, less than the text length if some were dropped
+     */
+    public static native int commitText(String text);
+
+    /**
+     * Delete text around the container's cursor, as InputConnection.deleteSurroundingText
+     * @param beforeLength characters to remove before the cursor, sent as backspace
+     * @param afterLength  characters to remove after the cursor, sent as forward delete
+     */
+    public static native void deleteSurroundingText(int beforeLength, int afterLength);
+
+    /**
+     * Press or release a button of the virtual gamepad
+     * @param button linux BTN_* code
+     * @param pressed 1 to press, 0 to release
+     */
+    public static native void sendGamepadButton(int button, int pressed);
+
+    /**
+     * Move an axis of the virtual gamepad
+     * @param axis linux ABS_* code
+     * @param value sticks -32768..32767, triggers 0..255, d-pad -1..1
+     */
+    public static native void sendGamepadAxis(int axis, int value);
+
+    /**
+     * Move several axes of the virtual gamepad, reported to the container as one frame
+     * @param axes pairs of linux ABS_* code and value, ranges as for {@link #sendGamepadAxis}
+     */
+    public static native void sendGamepadAxes(int[] axes);
+
+    /**
+     * Report a virtual mouse update
+     * @param dx relative horizontal motion
+     * @param dy relative vertical motion
+     * @param wheel vertical scroll detents, positive is up
+     * @param hwheel horizontal scroll detents, positive is right
+     * @param buttons held buttons: bit 0 left, 1 right, 2 middle, 3 back, 4 forward
+     */
+    public static native void sendMouseEvent(int dx, int dy, int wheel, int hwheel, int buttons);
+
+    /**
+     * Scroll at the virtual mouse pointer by whole wheel detents
+     * @param dx horizontal detents, positive is right
+     * @param dy vertical detents, positive is up
+     */
+    public static native void sendScroll(int dx, int dy);
+
+    /**
+     * Report the virtual stylus
+     * @param x position in virtual display pixels
+     * @param y position in virtual display pixels
+     * @param pressure 0.0 to 1.0
+     * @param tiltX tilt in degrees, positive towards the right
+     * @param tiltY tilt in degrees, positive towards the user
+     * @param state 0 out of range, 1 hovering, 2 touching
+     * @param button 1 while the barrel button is held
+     */
+    public static native void sendStylusEvent(int x, int y, float pressure, int tiltX, int tiltY, int state, int button);
+
+    /**
+     * Start recording every event forwarded to the virtual input devices
+     */
+    public static native void startInputRecording();
+
+    /**
+     * Stop the recording started by {@link #startInputRecording()}
+     * @return id of the recording, or -1 if none was in progress
+     */
+    public static native int stopInputRecording();
+
+    /**
+     * Replay a recording with its original timing
+     * @param id id returned by {@link #stopInputRecording()}
+     * @param speed playback speed, 2.0 replays twice as fast, clamped to 0.1 - 10.0
+     * @return false if there is no such recording, or it has touches and a finger is down
+     */
+    public static native boolean replayInput(int id, float speed);
+
+    /**
+     * Forget a recording
+     * @param id id returned by {@link #stopInputRecording()}
+     * @return false if there is no such recording
+     */
+    public static native boolean deleteInputRecording(int id);
+
+    /**
+     * Forget every recording, ids are not reused
+     */
+    public static native void clearInputRecordings();
+
+    /**
+     * Enable or disable input latency measurement, clearing previous samples
+     * @param enabled 1 to measure every input frame from submission to socket write
+     */
+    public static native void setInputLatencyMode(int enabled);
+
+    /**
+     * Input latency over the most recent frames
+     * @return JSON object with the sample count and p50/p90/p99/max in microseconds
+     */
+    public static native String getInputLatency();
     
+    /** The container's init exited on its own, detail is its exit status */
+    public static final String EVENT_CONTAINER_EXITED = "container_exited";
+    /** The renderer failed to start, detail is its result code */
+    public static final String EVENT_RENDERER_ERROR = "renderer_error";
+    /** A tombstone or ANR trace was written, detail is JSON with "process" and "path" of the copy under crashes/ */
+    public static final String EVENT_APP_CRASHED = "app_crashed";
+
+    /**
+     * Receives events from native code.
+     * Called on a native thread, post to the main thread before touching UI.
+     */
+    public interface EventListener {
+        void onEvent(String event, String detail);
+    }
+
+    /**
+     * Register the listener for native events, replacing any previous one
+     * @param listener null to stop delivery
+     */
+    public static native void setEventListener(EventListener listener);
+
     /**
      * Set the renderer type to use
      * @param useNewRenderer true to use new open-source renderer, false for old renderer
      */
     public static native void setRendererType(int useNewRenderer);
 
+    /**
+     * Select the renderer by backend name, same as {@link #setRendererType} for the backends it knows
+     * @param backend "emugl" for the embedded renderer or "new" for the open-source one;
+     *                "gfxstream" and "software" are not available in this build
+     * @return false if the backend is unknown or not available
+     */
+    public static native boolean setRendererBackend(String backend);
+
     /**
      * Set debug renderer mode
      * @param debugEnabled true to enable debug logging, false to disable
@@ -58,4 +280,87 @@ public class Renderer {
      * @param logDir absolute path to the directory where debug logs should be written
      */
     public static native void setDebugLogDir(String logDir);
+
+    /**
+     * Set the container hostname, applied when the container can get its own UTS namespace.
+     * Must be called before {@link #init}.
+     * @param hostname hostname reported inside the container
+     */
+    public static native void setContainerHostname(String hostname);
+
+    /**
+     * Bind-mount a host directory into the container. Must be called before {@link #init}.
+     * Bind mounts need CAP_SYS_ADMIN, so this fails for a regular, unprivileged install.
+     * @param hostDir      existing directory on the host
+     * @param containerDir absolute path inside the container, e.g. /data/media/0/Download
+     * @return false if the folder cannot be shared
+     */
+    public static native boolean addSharedFolder(String hostDir, String containerDir);
+
+    /**
+     * Configure rotation of the container console log, log.txt.
+     * The previous run's log is kept as log.txt.1 (log.txt.1.gz when compressed) and older ones shift up.
+     * @param maxKb      size at which the running log is rotated, 0 for no size limit; defaults to 4096
+     * @param maxMinutes age at which the running log is rotated, 0 for no age limit; defaults to 0
+     * @param keep       rotated files to keep, 0 to keep none; defaults to 3
+     * @param compress   1 to gzip rotated files, 0 to keep them plain; defaults to 1
+     */
+    public static native void setLogRotation(int maxKb, int maxMinutes, int keep, int compress);
+
+    /**
+     * Set the seccomp profile installed on the container. Must be called before {@link #init}.
+     * @param profile 0 to disable filtering, 1 for permissive (the default), 2 for strict
+     */
+    public static native void setSeccompProfile(int profile);
+
+    /**
+     * Propagate the host timezone and locale into the container's persistent properties.
+     * Must be called before {@link #init}.
+     * @param timezone Olson timezone id, e.g. "Asia/Shanghai"
+     * @param locale BCP 47 language tag, e.g. "zh-CN"
+     */
+    public static native void setHostTimeSettings(String timezone, String locale);
+
+    /**
+     * Pause every process in the container, keeping its state
+     */
+    public static native void freezeContainer();
+
+    /**
+     * Resume a container paused by {@link #freezeContainer()}
+     */
+    public static native void thawContainer();
+
+    /**
+     * Stop every process in the container, killing those that ignore SIGTERM for 5 seconds.
+     * Blocks until the container has exited, do not call on the main thread.
+     * @return false if the container was not running or could not be stopped
+     */
+    public static native boolean stopContainer();
+
+    /**
+     * Stop the container if it is running and boot it again with the same loader.
+     * Blocks while the container stops, do not call on the main thread.
+     * @return false if the container could not be restarted
+     */
+    public static native boolean restartContainer();
+
+    /**
+     * Cheap liveness check of the container, input devices and renderer
+     * @return JSON object with a top-level "healthy" flag and per-component results
+     */
+    public static native String checkHealth();
+
+    /**
+     * Container supervision state for status displays
+     * @return JSON object with "running", "pid", "uptime_ms", "boot_completed", "renderer_started" and "frames";
+     *         pid and uptime are null when stopped, frames is null unless the open-source renderer is running
+     */
+    public static native String getContainerStatus();
+
+    /**
+     * Tell the native side whether the guest has finished booting, reported by {@link #getContainerStatus}
+     * @param completed 1 once booted, 0 when the container is reset
+     */
+    public static native void setBootCompleted(int completed);
 }
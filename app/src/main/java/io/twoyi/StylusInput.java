@@ -0,0 +1,71 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+
+package io.twoyi;
+
+import android.view.MotionEvent;
+
+/**
+ * Forwards a host stylus, including hover, pressure and tilt, to the
+ * container's virtual stylus instead of the touch device.
+ */
+public final class StylusInput {
+
+    private static final int STATE_AWAY = 0;
+    private static final int STATE_HOVER = 1;
+    private static final int STATE_CONTACT = 2;
+
+    private StylusInput() {
+    }
+
+    /**
+     * @param scaleX surface to virtual display scale
+     * @param scaleY surface to virtual display scale
+     * @return true if the event came from a stylus and was forwarded
+     */
+    public static boolean handleMotionEvent(MotionEvent event, float scaleX, float scaleY) {
+        int toolType = event.getToolType(0);
+        if (toolType != MotionEvent.TOOL_TYPE_STYLUS && toolType != MotionEvent.TOOL_TYPE_ERASER) {
+            return false;
+        }
+
+        int state;
+        switch (event.getActionMasked()) {
+            case MotionEvent.ACTION_DOWN:
+            case MotionEvent.ACTION_MOVE:
+                state = STATE_CONTACT;
+                break;
+            case MotionEvent.ACTION_UP:
+            case MotionEvent.ACTION_HOVER_ENTER:
+            case MotionEvent.ACTION_HOVER_MOVE:
+                state = STATE_HOVER;
+                break;
+            case MotionEvent.ACTION_CANCEL:
+            case MotionEvent.ACTION_HOVER_EXIT:
+                state = STATE_AWAY;
+                break;
+            default:
+                return true;
+        }
+
+        // Android reports tilt from vertical plus the direction it leans in
+        double tilt = Math.toDegrees(event.getAxisValue(MotionEvent.AXIS_TILT));
+        double orientation = event.getOrientation();
+        int tiltX = (int) Math.round(tilt * Math.sin(orientation));
+        int tiltY = (int) Math.round(-tilt * Math.cos(orientation));
+
+        boolean button = (event.getButtonState() & MotionEvent.BUTTON_STYLUS_PRIMARY) != 0;
+        Renderer.sendStylusEvent(
+                Math.round(event.getX() * scaleX),
+                Math.round(event.getY() * scaleY),
+                event.getPressure(),
+                tiltX,
+                tiltY,
+                state,
+                button ? 1 : 0);
+        return true;
+    }
+}
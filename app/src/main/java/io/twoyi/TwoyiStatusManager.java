@@ -49,6 +49,7 @@ public class TwoyiStatusManager {
 
     public void markStarted() {
         if (mStarted.compareAndSet(false, true)) {
+            Renderer.setBootCompleted(1);
             try {
                 mBootLatch.await();
             } catch (BrokenBarrierException | InterruptedException e) {
@@ -64,6 +65,7 @@ public class TwoyiStatusManager {
     public void reset() {
         mStarted.set(false);
         mBootLatch.reset();
+        Renderer.setBootCompleted(0);
     }
 
     public boolean waitBoot(long timeout, TimeUnit unit) throws InterruptedException, BrokenBarrierException {
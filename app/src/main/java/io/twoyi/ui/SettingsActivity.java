@@ -120,6 +120,8 @@ public class SettingsActivity extends AppCompatActivity {
             Preference displayDpi = findPreference(R.string.settings_key_display_dpi);
             CheckBoxPreference useNewRenderer = (CheckBoxPreference) findPreference(R.string.settings_key_use_new_renderer);
             CheckBoxPreference debugRenderer = (CheckBoxPreference) findPreference(R.string.settings_key_debug_renderer);
+            CheckBoxPreference syncHostTime = (CheckBoxPreference) findPreference(R.string.settings_key_sync_host_time);
+            CheckBoxPreference seccompFilter = (CheckBoxPreference) findPreference(R.string.settings_key_seccomp_filter);
             Preference selectRom = findPreference(R.string.settings_key_select_rom);
             Preference factoryReset = findPreference(R.string.settings_key_factory_reset);
 
@@ -215,6 +217,22 @@ public class SettingsActivity extends AppCompatActivity {
                 return true;
             });
 
+            // Initialize timezone sync checkbox with profile-specific value
+            syncHostTime.setChecked(ProfileSettings.isSyncHostTimeEnabled(getActivity()));
+            syncHostTime.setOnPreferenceChangeListener((preference, newValue) -> {
+                ProfileSettings.setSyncHostTime(getActivity(), (Boolean) newValue);
+                Toast.makeText(getActivity(), R.string.settings_display_change_reboot, Toast.LENGTH_SHORT).show();
+                return true;
+            });
+
+            // Initialize syscall filter checkbox with profile-specific value
+            seccompFilter.setChecked(ProfileSettings.isSeccompFilterEnabled(getActivity()));
+            seccompFilter.setOnPreferenceChangeListener((preference, newValue) -> {
+                ProfileSettings.setSeccompFilter(getActivity(), (Boolean) newValue);
+                Toast.makeText(getActivity(), R.string.settings_display_change_reboot, Toast.LENGTH_SHORT).show();
+                return true;
+            });
+
             launchContainer.setOnPreferenceClickListener(preference -> {
                 Intent intent = new Intent(getContext(), io.twoyi.Render2Activity.class);
                 startActivity(intent);
@@ -32,6 +32,8 @@ public class ProfileSettings {
     public static final String DISPLAY_DPI = "display_dpi";
     public static final String USE_NEW_RENDERER = "use_new_renderer";
     public static final String DEBUG_RENDERER = "debug_renderer";
+    public static final String SYNC_HOST_TIME = "sync_host_time";
+    public static final String SECCOMP_FILTER = "seccomp_filter";
 
     /**
      * Get SharedPreferences for the active profile
@@ -187,4 +189,32 @@ public class ProfileSettings {
     public static void setDebugRenderer(Context context, boolean enabled) {
         setBoolean(context, DEBUG_RENDERER, enabled);
     }
+
+    /**
+     * Check if host timezone and locale should be propagated into the container (default: false)
+     */
+    public static boolean isSyncHostTimeEnabled(Context context) {
+        return getBoolean(context, SYNC_HOST_TIME, false);
+    }
+
+    /**
+     * Set host timezone and locale propagation for active profile
+     */
+    public static void setSyncHostTime(Context context, boolean enabled) {
+        setBoolean(context, SYNC_HOST_TIME, enabled);
+    }
+
+    /**
+     * Check if the container should run under a seccomp syscall filter (default: true)
+     */
+    public static boolean isSeccompFilterEnabled(Context context) {
+        return getBoolean(context, SECCOMP_FILTER, true);
+    }
+
+    /**
+     * Set seccomp syscall filtering for active profile
+     */
+    public static void setSeccompFilter(Context context, boolean enabled) {
+        setBoolean(context, SECCOMP_FILTER, enabled);
+    }
 }
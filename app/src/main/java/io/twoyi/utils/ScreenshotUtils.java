@@ -0,0 +1,57 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+
+package io.twoyi.utils;
+
+import android.graphics.Bitmap;
+import android.os.Handler;
+import android.os.Looper;
+import android.util.Log;
+import android.view.PixelCopy;
+import android.view.SurfaceView;
+
+import androidx.annotation.Nullable;
+
+/**
+ * Captures the container display as a Bitmap.
+ *
+ * Neither renderer exposes its frames to the host, so the frame is copied
+ * back from the SurfaceView it was presented to.
+ */
+public class ScreenshotUtils {
+
+    private static final String TAG = "ScreenshotUtils";
+
+    public interface OnScreenshotListener {
+        /**
+         * @param bitmap the current frame, or null if it could not be captured
+         */
+        void onScreenshot(@Nullable Bitmap bitmap);
+    }
+
+    /**
+     * Capture the current frame of {@code surfaceView}, the listener is called on the main thread
+     */
+    public static void capture(SurfaceView surfaceView, OnScreenshotListener listener) {
+        int width = surfaceView.getWidth();
+        int height = surfaceView.getHeight();
+        if (width <= 0 || height <= 0 || !surfaceView.getHolder().getSurface().isValid()) {
+            listener.onScreenshot(null);
+            return;
+        }
+
+        Bitmap bitmap = Bitmap.createBitmap(width, height, Bitmap.Config.ARGB_8888);
+        PixelCopy.request(surfaceView, bitmap, result -> {
+            if (result == PixelCopy.SUCCESS) {
+                listener.onScreenshot(bitmap);
+            } else {
+                Log.w(TAG, "PixelCopy failed: " + result);
+                bitmap.recycle();
+                listener.onScreenshot(null);
+            }
+        }, new Handler(Looper.getMainLooper()));
+    }
+}